@@ -66,6 +66,13 @@
                                                   transacted-course))))
                                courses))
 
+; the parser keeps its manifest as a hidden file next to the courses
+(defn course-json-file? [file]
+  (let [name (.getName file)]
+    (and (.isFile file)
+         (.endsWith name ".json")
+         (not (.startsWith name ".")))))
+
 (defn read-json-file [file-name]
   (let [file (slurp file-name)]
     (json/read-str file)))
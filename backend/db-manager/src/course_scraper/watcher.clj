@@ -6,8 +6,8 @@
             [clojure.java.shell :as shell]
             [datascript.core :as d]
             [db-manager.db :refer [schema]]
-            [exam-scraper.core :refer [get-itx-courses-from-dir patch-courses-w-itx]]
-            [course-scraper.upsert :refer [try-finding-stats transactions-w-stats read-json-file]])
+            [exam-scraper.core :refer [convert-exam-pdfs-in-dir]]
+            [course-scraper.upsert :refer [try-finding-stats transactions-w-stats read-json-file course-json-file?]])
   (:import (javax.net.ssl SSLEngine SSLParameters SNIHostName)
            (java.net URI))
 
@@ -17,6 +17,7 @@
 (def pages-dir "../../data/pages")
 (def json-dir "../../data/new_json")
 (def exam-pdfs-dir "../../data/exam_pdfs")
+(def exam-schedules-dir "../../data/exam_schedules")
 (def stats-dir (str data-dir "statistics/"))
 
 
@@ -82,7 +83,9 @@
     (println "[course scraper]: Modified" (count @newly-scraped) "courses")
 
     (if-not (zero? (count @newly-scraped))
-      (let [result (future (shell/sh "rust_parser" pages-dir json-dir))]
+      (let [_ (convert-exam-pdfs-in-dir exam-pdfs-dir exam-schedules-dir)
+            result (future (shell/sh "rust_parser" "parse-dir" pages-dir json-dir
+                                     "--exam-schedules" exam-schedules-dir))]
         (println "[course parser] Running rust parser...")
         (println "[course parser] Parser stderr: " (:err @result))
         (println "[course parser] Finished parsing courses"))
@@ -93,13 +96,11 @@
     (let [stats-finder #(try-finding-stats stats-dir %)
           ; FIXME: we already know whihc courses to take, this does extra work
           ; this currently takes all courses instead of updating the ones that are new
-          courses (map read-json-file (drop 1 (file-seq (clojure.java.io/file json-dir))))
-          itx-course-ids (get-itx-courses-from-dir exam-pdfs-dir)
-          patched-courses (patch-courses-w-itx courses itx-course-ids)]
+          courses (map read-json-file (filter course-json-file? (file-seq (clojure.java.io/file json-dir))))]
       ; FIXME: this is a hack and we should just drop the workflows and exams
       (d/reset-conn! conn (d/empty-db schema))
 
-      (d/transact! conn (transactions-w-stats stats-finder patched-courses)))
+      (d/transact! conn (transactions-w-stats stats-finder courses)))
     (println "[course scraper]: Finished updating database")
 
     (reset! newly-scraped [])
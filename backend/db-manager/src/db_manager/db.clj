@@ -16,6 +16,7 @@
 (def schema
   {:course/id unique
    :course/title {}
+   :course/status {}
    :course/ects {}
 
    :course/block many-ref
@@ -46,6 +47,9 @@
    :language/name unique
    :employee/email unique
    :employee/name {}
+   :employee/given-name {}
+   :employee/family-name {}
+   :employee/email-kind {}
    :statistics/mean {:db/cardinality :db.cardinality/one}
    :statistics/median {:db/cardinality :db.cardinality/one}
    :statistics/pass-rate {:db/cardinality :db.cardinality/one}})
@@ -54,9 +58,16 @@
   "Convert a coordinator map from rust parser to a datascript map"
   [coordinator]
   (let [name (get coordinator "name")
-        email (get coordinator "email")]
-    {:employee/name name
-     :employee/email email}))
+        email (get coordinator "email")
+        ; the split names and the email kind are missing in json from older parsers
+        given-name (get coordinator "given_name")
+        family-name (get coordinator "family_name")
+        email-kind (get coordinator "email_kind")]
+    (cond-> {:employee/name name
+             :employee/email email}
+      (seq given-name) (assoc :employee/given-name given-name)
+      (seq family-name) (assoc :employee/family-name family-name)
+      email-kind (assoc :employee/email-kind email-kind))))
 (defn convert-workload
   "Convert a workload map from rust parser to a datascript map"
   [workload]
@@ -91,6 +102,8 @@
 (defn course-to-transaction  [course-map]
   (let [id (get-in course-map ["info" "id"])
         title (get course-map "title")
+        ; one of Active, Cancelled, NotOffered or ClosedForRegistration
+        status (get course-map "status")
         ects (get-in course-map ["info" "ects"])
         blocks (get-in course-map ["info" "block"])
         schedules (get-in course-map ["info" "schedule"])
@@ -112,6 +125,7 @@
       (println "Course " title " has no departments"))
     {:course/id id
      :course/title title
+     :course/status status
      :course/ects ects
      :course/block (mapv #(hash-map :block/type %) blocks)
      :course/schedule (mapv #(hash-map :schedule/type %) schedules)
@@ -119,8 +133,10 @@
      :course/duration duration
      :course/degree (mapv #(hash-map :degree/type %) degrees)
      :course/capacity capacity
-     :course/department (mapv #(hash-map :department/name %) departments)
-     :course/faculty (hash-map :faculty/name faculty)
+     ; departments missing from the organisation mapping are {"Unknown" name}, they keep the name of the page
+     :course/department (mapv #(hash-map :department/name (if (map? %) (get % "Unknown") %)) departments)
+     ; like the departments, faculties missing from the mapping are {"Unknown" name}
+     :course/faculty (hash-map :faculty/name (if (map? faculty) (get faculty "Unknown") faculty))
      :course/coordinator coordinators
      :course/workload workloads
      :course/exam exams
@@ -1,7 +1,6 @@
 (ns exam-scraper.core
   (:require [clojure.java.io :as io]
-            [clojure.string :as string]
-            [clojure.set :as set])
+            [clojure.string :as string])
   (:import (java.io File)
           (org.apache.commons.cli DefaultParser)
           (technology.tabula CommandLineApp)))
@@ -19,47 +18,20 @@
         cli-app (CommandLineApp. System/out cmd-line)]
         (.extractFileInto cli-app pdf-file out-file)))
 
-(defn get-itx-courses-from-file [pdf-file]
-  ; the course code is on the first column, if the second column contains "ITX" anywheer in the row it's an ITX course
-  ; start by converting to tsv at a temporary location
-  (let [tsv-file (File/createTempFile "tabula" ".tsv")]
-    (try
-      (convert-exam-pdf-to-tsv pdf-file tsv-file)
-      (let [tsv (slurp tsv-file)
-            lines (string/split-lines tsv)
-            itx-courses (filter #(string/includes? % "ITX") lines)]
-        (println "[exam scraper] Found" (count itx-courses) "ITX courses in" pdf-file)
-        (map #(first (string/split % #"\t")) itx-courses))
-      (catch Exception e
-        (println "[exam scraper] Failed to extract ITX courses from" pdf-file ":" (.getMessage e))
-        (.printStackTrace e)
-        ; we don't handle this case yet,
-        ; we just return an empty list and move on
-        []))))
-
-(defn get-itx-courses-from-dir [dir]
-  (let [pdf-files (drop 1 (file-seq (io/file dir)))
-        itx-courses (mapcat get-itx-courses-from-file pdf-files)]
-    (distinct itx-courses)))
-
-
-(defn to-itx [exams-list]
-  ; exams-list is a vector of maps, each key has a key, if this key is "Written", change it to "ITX")
-  (map (fn [exam]
-         (if (map? exam)
-           (set/rename-keys exam {"Written" "ITX"})
-              exam)) exams-list))
-
-; I mistankenly thought they were a vector of maps, but they are a vector of maps OR strings
-
-
-; make a functio nthat only does this for a single course
-(defn patch-course-exam [course itx-course-ids]
-  (let [course-id (get-in course ["info" "id"])
-        itx? (some #(= course-id %) itx-course-ids)]
-    (if itx?
-      (assoc course "exams" (to-itx (get course "exams")))
-      course)))
-
-(defn patch-courses-w-itx [courses itx-course-ids]
-    (map #(patch-course-exam % itx-course-ids) courses))
+(defn convert-exam-pdfs-in-dir
+  "Converts every exam schedule pdf in pdf-dir to a tsv in tsv-dir,
+  the rust parser reads the ITX exams from the tsv files"
+  [pdf-dir tsv-dir]
+  (.mkdirs (io/file tsv-dir))
+  (doseq [^File pdf-file (filter #(.isFile ^File %) (file-seq (io/file pdf-dir)))]
+    (let [tsv-file (io/file tsv-dir (string/replace (.getName pdf-file) #"(?i)\.pdf$" ".tsv"))]
+      ; the schedules rarely change, so only convert them once
+      (when (< (.lastModified tsv-file) (.lastModified pdf-file))
+        (try
+          (convert-exam-pdf-to-tsv pdf-file tsv-file)
+          (println "[exam scraper] Converted" (.getName pdf-file))
+          (catch Exception e
+            (println "[exam scraper] Failed to convert" pdf-file ":" (.getMessage e))
+            (.printStackTrace e)
+            ; a partial tsv would be read as the schedule
+            (io/delete-file tsv-file true)))))))
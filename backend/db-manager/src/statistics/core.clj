@@ -121,7 +121,8 @@
 ; find all jsons
 ; TODO: refactor this since we arent using the start block anymore
 (def course-infos-init (for [file (file-seq (io/file json-dir))
-                             :when (.endsWith (.getName file) ".json")]
+                             :when (and (.endsWith (.getName file) ".json")
+                                        (not (.startsWith (.getName file) ".")))]
                          (read-json (.getName file))))
 ;(def course-infos-init [{:course-id "NNEB19009U"}])
 
@@ -0,0 +1,44 @@
+// Benchmarks parsing the test pages, run with `cargo bench` before and after changing the parser
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rust_parser::parser::parse_course;
+
+fn pages() -> Vec<(String, String)> {
+    let mut pages: Vec<_> = std::fs::read_dir("./test_data/pages")
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            (name, std::fs::read_to_string(path).unwrap())
+        })
+        .collect();
+    pages.sort();
+    pages
+}
+
+fn bench_pages(c: &mut Criterion) {
+    let pages = pages();
+    let mut group = c.benchmark_group("parse_course");
+    for (name, html) in &pages {
+        group.throughput(Throughput::Bytes(html.len() as u64));
+        // the pages that fail to parse are benchmarked as well, they are skipped just as often
+        group.bench_with_input(BenchmarkId::from_parameter(name), html, |b, html| {
+            b.iter(|| parse_course(black_box(html)))
+        });
+    }
+    group.finish();
+
+    let bytes = pages.iter().map(|(_, html)| html.len() as u64).sum();
+    let mut group = c.benchmark_group("catalogue");
+    group.throughput(Throughput::Bytes(bytes));
+    group.bench_function("every_page", |b| {
+        b.iter(|| {
+            for (_, html) in &pages {
+                let _ = parse_course(black_box(html));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_pages);
+criterion_main!(benches);
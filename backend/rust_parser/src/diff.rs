@@ -0,0 +1,289 @@
+// Compares two catalogues written by the parser, e.g. the runs before and after KU publishes
+// a new academic year, to see which courses were added, removed or changed and how
+use crate::parser::schema::read_course;
+use crate::parser::{Course, CourseCode};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+// Values longer than this are not printed in the readable report, only that they changed
+const MAX_PRINTED: usize = 80;
+
+/// The changeset between two catalogues
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CatalogueDiff {
+    pub added: Vec<CourseCode>,
+    pub removed: Vec<CourseCode>,
+    pub changed: Vec<CourseChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CourseChange {
+    pub id: CourseCode,
+    pub fields: Vec<FieldChange>,
+}
+
+/// A field that differs between the catalogues, in the same notation as the provenance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+/// Reads the courses of a json directory or an NDJSON file, keyed by their course code
+pub fn load_catalogue(path: &Path) -> Result<BTreeMap<CourseCode, Course>> {
+    let mut courses = BTreeMap::new();
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            let file = entry?.path();
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            // the manifest and the other hidden files are not courses
+            if name.starts_with('.') || file.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let json = std::fs::read_to_string(&file)
+                .with_context(|| format!("Could not read {}", file.display()))?;
+            let record =
+                read_course(&json).with_context(|| format!("Invalid course {}", file.display()))?;
+            courses.insert(record.course.info.id.clone(), record.course);
+        }
+    } else {
+        let ndjson = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        for (i, line) in ndjson.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = read_course(line).with_context(|| {
+                format!("Invalid course on line {} of {}", i + 1, path.display())
+            })?;
+            courses.insert(record.course.info.id.clone(), record.course);
+        }
+    }
+    Ok(courses)
+}
+
+// The fields that are compared, the summary is left out since it is cut from the content
+fn fields(course: &Course) -> Vec<(&'static str, Value)> {
+    let (info, logistics, description) = (&course.info, &course.logistics, &course.description);
+    vec![
+        ("title", value(&course.title)),
+        ("english_title", value(&course.english_title)),
+        ("status", value(&course.status)),
+        ("info.ects", value(&info.ects)),
+        ("info.block", value(&info.block)),
+        ("info.schedule", value(&info.schedule)),
+        ("info.language", value(&info.language)),
+        ("info.duration", value(&info.duration)),
+        ("info.degree", value(&info.degree)),
+        ("info.capacity", value(&info.capacity)),
+        ("logistics.departments", value(&logistics.departments)),
+        ("logistics.faculty", value(&logistics.faculty)),
+        ("logistics.coordinators", value(&logistics.coordinators)),
+        ("workloads", value(&course.workloads)),
+        ("exams", value(&course.exams)),
+        ("description.content", value(&description.content)),
+        (
+            "description.learning_outcome",
+            value(&description.learning_outcome),
+        ),
+        (
+            "description.recommended_qualifications",
+            value(&description.recommended_qualifications),
+        ),
+        ("raw_fields", value(&course.raw_fields)),
+    ]
+}
+
+fn value<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+/// The fields that differ between two versions of a course
+pub fn diff_course(before: &Course, after: &Course) -> Vec<FieldChange> {
+    fields(before)
+        .into_iter()
+        .zip(fields(after))
+        .filter(|((_, before), (_, after))| before != after)
+        .map(|((field, before), (_, after))| FieldChange {
+            field: field.to_string(),
+            before,
+            after,
+        })
+        .collect()
+}
+
+pub fn diff_catalogues(
+    before: &BTreeMap<CourseCode, Course>,
+    after: &BTreeMap<CourseCode, Course>,
+) -> CatalogueDiff {
+    let mut diff = CatalogueDiff::default();
+    for (id, course) in after {
+        match before.get(id) {
+            None => diff.added.push(id.clone()),
+            Some(old) => {
+                let fields = diff_course(old, course);
+                if !fields.is_empty() {
+                    diff.changed.push(CourseChange {
+                        id: id.clone(),
+                        fields,
+                    });
+                }
+            }
+        }
+    }
+    diff.removed = before
+        .keys()
+        .filter(|id| !after.contains_key(*id))
+        .cloned()
+        .collect();
+    diff
+}
+
+impl CatalogueDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// How many courses changed in each field, most first
+    pub fn field_counts(&self) -> Vec<(&str, usize)> {
+        let mut counts = BTreeMap::<&str, usize>::new();
+        for change in &self.changed {
+            for field in &change.fields {
+                *counts.entry(field.field.as_str()).or_default() += 1;
+            }
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        counts
+    }
+}
+
+fn printed(value: &Value) -> String {
+    let printed = match value {
+        Value::String(s) => format!("{s:?}"),
+        _ => value.to_string(),
+    };
+    if printed.chars().count() > MAX_PRINTED {
+        "…".to_string()
+    } else {
+        printed
+    }
+}
+
+// The readable report of the diff
+impl fmt::Display for CatalogueDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids = |ids: &[CourseCode]| {
+            ids.iter()
+                .map(CourseCode::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        writeln!(
+            f,
+            "{} added, {} removed, {} changed",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        )?;
+        if !self.added.is_empty() {
+            writeln!(f, "added: {}", ids(&self.added))?;
+        }
+        if !self.removed.is_empty() {
+            writeln!(f, "removed: {}", ids(&self.removed))?;
+        }
+        for (field, count) in self.field_counts() {
+            writeln!(f, "{count:>6} {field}")?;
+        }
+        for change in &self.changed {
+            writeln!(f, "{}", change.id)?;
+            for field in &change.fields {
+                writeln!(
+                    f,
+                    "    {}: {} -> {}",
+                    field.field,
+                    printed(&field.before),
+                    printed(&field.after)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_course, test_course, test_page, Block, Coordinator, Exam};
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_catalogues() {
+        let (a, b) = (test_course("LSLS10061U"), test_course("NDAB15009U"));
+        let mut changed = a.clone();
+        changed.info.ects = 15.0;
+        changed.info.block = vec![Block::Three];
+        changed.exams = vec![Exam::Written(Some(240))];
+        changed
+            .logistics
+            .coordinators
+            .push(Coordinator::new("Some Body".into(), "abc123@ku.dk".into()));
+        let removed = test_course("LSLS10156U");
+
+        let before = BTreeMap::from([
+            (a.info.id.clone(), a.clone()),
+            (removed.info.id.clone(), removed.clone()),
+        ]);
+        let after = BTreeMap::from([
+            (changed.info.id.clone(), changed.clone()),
+            (b.info.id.clone(), b.clone()),
+        ]);
+        let diff = diff_catalogues(&before, &after);
+        assert_eq!(diff.added, vec![b.info.id.clone()]);
+        assert_eq!(diff.removed, vec![removed.info.id.clone()]);
+        assert_eq!(diff.changed.len(), 1);
+
+        let fields = &diff.changed[0].fields;
+        let names: Vec<_> = fields.iter().map(|f| f.field.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["info.ects", "info.block", "logistics.coordinators", "exams"]
+        );
+        assert_eq!(
+            fields[0],
+            FieldChange {
+                field: "info.ects".into(),
+                before: json!(7.5),
+                after: json!(15.0),
+            }
+        );
+
+        let report = diff.to_string();
+        assert!(report.starts_with("1 added, 1 removed, 1 changed\n"));
+        assert!(report.contains("    info.block: [\"Two\"] -> [\"Three\"]\n"));
+        assert!(diff_catalogues(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn test_load_catalogue() {
+        let dir =
+            std::env::temp_dir().join(format!("rust_parser_load_catalogue_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let parsed = parse_course(&test_page("LSLS10061U")).unwrap();
+        let json = serde_json::to_string(&parsed.output()).unwrap();
+        std::fs::write(dir.join("LSLS10061U.json"), &json).unwrap();
+        std::fs::write(dir.join(".manifest.json"), "{}").unwrap();
+        std::fs::write(dir.join("courses.ndjson"), format!("{json}\n\n")).unwrap();
+
+        let from_dir = load_catalogue(&dir).unwrap();
+        let from_ndjson = load_catalogue(&dir.join("courses.ndjson")).unwrap();
+        assert_eq!(from_dir.len(), 1);
+        assert_eq!(from_dir, from_ndjson);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,541 @@
+// Fetches course pages into the pages directory without hammering kurser.ku.dk: requests are rate limited,
+// failures are retried with backoff and pages that did not change are not downloaded again
+use crate::manifest::PARSER_VERSION;
+use crate::sitemap::SitemapEntry;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// The ETag and Last-Modified of every fetched page, sent back to only download pages that changed
+pub const FETCH_STATE_FILE: &str = ".fetch-state.json";
+/// A line of json for every page that was fetched, appended to by every run
+pub const FETCH_LOG_FILE: &str = ".fetch-log.ndjson";
+
+// Waiting longer than this for a Retry-After is not worth it, the page is fetched on the next run instead
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone)]
+pub struct FetchOptions {
+    /// The most requests per second, across all threads
+    pub rate: f64,
+    /// How many pages are fetched at once
+    pub concurrency: usize,
+    /// How many times a page is retried after a server error or a timeout
+    pub retries: u32,
+    /// The wait before the first retry, it doubles with every retry
+    pub backoff: Duration,
+    pub timeout: Duration,
+}
+
+impl FetchOptions {
+    /// Checks the options before any page is fetched, a rate that is not a positive number
+    /// would never let a request through
+    pub fn validate(&self) -> Result<()> {
+        check_rate(self.rate).map_err(|e| anyhow!(e))?;
+        Ok(())
+    }
+}
+
+/// Parses the most requests per second of the command line
+pub fn parse_rate(rate: &str) -> Result<f64, String> {
+    let rate = rate
+        .parse()
+        .map_err(|_| format!("{rate} is not a number"))?;
+    check_rate(rate)
+}
+
+fn check_rate(rate: f64) -> Result<f64, String> {
+    if rate.is_finite() && rate > 0.0 {
+        Ok(rate)
+    } else {
+        Err(format!(
+            "the rate has to be above 0 requests per second, not {rate}"
+        ))
+    }
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            rate: 3.0,
+            concurrency: 2,
+            retries: 3,
+            backoff: Duration::from_secs(1),
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FetchState {
+    pub pages: BTreeMap<String, Validators>,
+}
+
+impl FetchState {
+    pub fn load(pages_dir: &Path) -> Result<Self> {
+        let path = pages_dir.join(FETCH_STATE_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let json = std::fs::read_to_string(&path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("Invalid fetch state {}", path.display()))
+    }
+
+    pub fn save(&self, pages_dir: &Path) -> Result<()> {
+        write_atomic(
+            pages_dir,
+            FETCH_STATE_FILE,
+            &serde_json::to_vec_pretty(self)?,
+        )
+    }
+}
+
+/// What happened to a page, a line of the fetch log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FetchRecord {
+    pub page: String,
+    pub url: String,
+    #[serde(flatten)]
+    pub outcome: FetchOutcome,
+    pub attempts: u32,
+    /// Seconds since the unix epoch
+    pub at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum FetchOutcome {
+    Fetched { status: u16 },
+    NotModified,
+    Failed { error: String },
+}
+
+// Hands out the times at which requests may be sent, so the threads together stay below the rate
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(rate: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / rate),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn wait(&self) {
+        let wait = {
+            let mut next = self.next.lock().unwrap();
+            let now = Instant::now();
+            let at = (*next).max(now);
+            *next = at + self.interval;
+            at - now
+        };
+        std::thread::sleep(wait);
+    }
+}
+
+// The file is written next to its destination and renamed, so a page is never read half written
+fn write_atomic(dir: &Path, name: &str, bytes: &[u8]) -> Result<()> {
+    let tmp = dir.join(format!(".{name}.tmp"));
+    std::fs::write(&tmp, bytes).with_context(|| format!("Could not write {}", tmp.display()))?;
+    let path = dir.join(name);
+    std::fs::rename(&tmp, &path).with_context(|| format!("Could not write {}", path.display()))
+}
+
+fn retry_after(response: &ureq::Response) -> Option<Duration> {
+    let seconds = response.header("Retry-After")?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds).min(MAX_RETRY_AFTER))
+}
+
+struct Fetcher<'a> {
+    agent: ureq::Agent,
+    pages_dir: &'a Path,
+    options: &'a FetchOptions,
+    limiter: RateLimiter,
+}
+
+impl Fetcher<'_> {
+    // Fetches a page with as many retries as needed, returns the outcome, the number of attempts
+    // and the validators of the page if it was downloaded
+    fn fetch(
+        &self,
+        entry: &SitemapEntry,
+        validators: Option<&Validators>,
+    ) -> (FetchOutcome, u32, Option<Validators>) {
+        let page = entry.page();
+        let path = self.pages_dir.join(&page);
+        // without the page there is nothing to compare against
+        let saved = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let validators = validators.filter(|_| saved.is_some());
+
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            self.limiter.wait();
+            let mut request = self.agent.get(&entry.loc);
+            if let Some(etag) = validators.and_then(|v| v.etag.as_deref()) {
+                request = request.set("If-None-Match", etag);
+            }
+            let last_modified = validators.and_then(|v| v.last_modified.clone());
+            if let Some(since) = last_modified.or(saved.map(httpdate::fmt_http_date)) {
+                request = request.set("If-Modified-Since", &since);
+            }
+
+            let (wait, error) = match request.call() {
+                Ok(response) if response.status() == 304 => {
+                    // the page is up to date, so it should not look modified to the next plan either
+                    let touched = std::fs::File::options()
+                        .write(true)
+                        .open(&path)
+                        .and_then(|file| file.set_modified(SystemTime::now()));
+                    return match touched {
+                        Ok(()) => (FetchOutcome::NotModified, attempts, None),
+                        Err(e) => (failed(e.into()), attempts, None),
+                    };
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let validators = Validators {
+                        etag: response.header("ETag").map(str::to_string),
+                        last_modified: response.header("Last-Modified").map(str::to_string),
+                    };
+                    let saved = response
+                        .into_string()
+                        .map_err(anyhow::Error::from)
+                        .and_then(|body| write_atomic(self.pages_dir, &page, body.as_bytes()));
+                    return match saved {
+                        Ok(()) => (FetchOutcome::Fetched { status }, attempts, Some(validators)),
+                        Err(e) => (failed(e), attempts, None),
+                    };
+                }
+                Err(ureq::Error::Status(status, response)) if status == 429 || status >= 500 => {
+                    (retry_after(&response), anyhow!("HTTP {status}"))
+                }
+                Err(ureq::Error::Status(status, _)) => {
+                    return (failed(anyhow!("HTTP {status}")), attempts, None);
+                }
+                Err(e) => (None, e.into()),
+            };
+            if attempts > self.options.retries {
+                return (failed(error), attempts, None);
+            }
+            let backoff = self.options.backoff * 2u32.saturating_pow(attempts - 1);
+            std::thread::sleep(wait.unwrap_or(backoff));
+        }
+    }
+}
+
+fn failed(error: anyhow::Error) -> FetchOutcome {
+    FetchOutcome::Failed {
+        error: format!("{error:#}"),
+    }
+}
+
+/// Fetches the pages into the pages directory, updates the fetch state and appends to the fetch log.
+/// The records are in the same order as the entries
+pub fn fetch_pages(
+    entries: &[SitemapEntry],
+    pages_dir: &Path,
+    options: &FetchOptions,
+) -> Result<Vec<FetchRecord>> {
+    options.validate()?;
+    std::fs::create_dir_all(pages_dir)?;
+    let state = Mutex::new(FetchState::load(pages_dir)?);
+    let log_path = pages_dir.join(FETCH_LOG_FILE);
+    let log = Mutex::new(
+        std::fs::File::options()
+            .create(true)
+            .append(true)
+            .open(&log_path)
+            .with_context(|| format!("Could not open {}", log_path.display()))?,
+    );
+    let fetcher = Fetcher {
+        agent: ureq::AgentBuilder::new()
+            .timeout(options.timeout)
+            .user_agent(&format!("rust_parser/{PARSER_VERSION}"))
+            .build(),
+        pages_dir,
+        options,
+        limiter: RateLimiter::new(options.rate),
+    };
+
+    // the threads take the next entry until there are none left
+    let next = AtomicUsize::new(0);
+    let records = Mutex::new(vec![None; entries.len()]);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..options.concurrency.max(1))
+            .map(|_| {
+                scope.spawn(|| -> Result<()> {
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(entry) = entries.get(i) else {
+                            return Ok(());
+                        };
+                        let page = entry.page();
+                        let known = state.lock().unwrap().pages.get(&page).cloned();
+                        let (outcome, attempts, validators) = fetcher.fetch(entry, known.as_ref());
+                        if let Some(validators) = validators {
+                            state.lock().unwrap().pages.insert(page.clone(), validators);
+                        }
+                        let record = FetchRecord {
+                            page,
+                            url: entry.loc.clone(),
+                            outcome,
+                            attempts,
+                            at: SystemTime::now()
+                                .duration_since(SystemTime::UNIX_EPOCH)
+                                .unwrap_or_default()
+                                .as_secs(),
+                        };
+                        writeln!(log.lock().unwrap(), "{}", serde_json::to_string(&record)?)?;
+                        records.lock().unwrap()[i] = Some(record);
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .try_for_each(|worker| worker.join().unwrap())
+    })?;
+    state.into_inner().unwrap().save(pages_dir)?;
+    Ok(records
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+/// How many pages had each outcome
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FetchSummary {
+    pub fetched: usize,
+    pub not_modified: usize,
+    pub failed: usize,
+}
+
+impl FetchSummary {
+    pub fn new(records: &[FetchRecord]) -> Self {
+        let mut summary = Self::default();
+        for record in records {
+            match record.outcome {
+                FetchOutcome::Fetched { .. } => summary.fetched += 1,
+                FetchOutcome::NotModified => summary.not_modified += 1,
+                FetchOutcome::Failed { .. } => summary.failed += 1,
+            }
+        }
+        summary
+    }
+}
+
+impl fmt::Display for FetchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} fetched, {} not modified, {} failed",
+            self.fetched, self.not_modified, self.failed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+    use std::sync::Arc;
+    use tiny_http::{Header, Request, Response, Server};
+
+    type StubResponse = Response<std::io::Cursor<Vec<u8>>>;
+
+    // A local server that answers every request with `respond`, returns its url
+    fn stub(respond: impl Fn(&Request) -> StubResponse + Send + 'static) -> String {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", server.server_addr());
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let response = respond(&request);
+                let _ = request.respond(response);
+            }
+        });
+        url
+    }
+
+    fn header(request: &Request, name: &'static str) -> Option<String> {
+        request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv(name))
+            .map(|h| h.value.to_string())
+    }
+
+    fn entries(url: &str, ids: &[&str]) -> Vec<SitemapEntry> {
+        ids.iter()
+            .map(|id| SitemapEntry {
+                loc: format!("{url}/course/{id}"),
+                id: id.to_string(),
+                lastmod: None,
+            })
+            .collect()
+    }
+
+    fn options() -> FetchOptions {
+        FetchOptions {
+            rate: 1000.0,
+            backoff: Duration::from_millis(1),
+            ..FetchOptions::default()
+        }
+    }
+
+    // The process id keeps test runs that happen at the same time out of each other's directories
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("{name}_{}", std::process::id()))
+    }
+
+    #[test]
+    fn test_rate() {
+        assert_eq!(parse_rate("0.5"), Ok(0.5));
+        for rate in ["0", "-1", "NaN", "inf", "fast"] {
+            assert!(parse_rate(rate).is_err(), "{rate}");
+        }
+        let options = FetchOptions {
+            rate: 0.0,
+            ..Default::default()
+        };
+        let dir = temp_dir("rust_parser_test_rate");
+        assert!(fetch_pages(&[], &dir, &options).is_err());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_conditional_fetch() {
+        let url = stub(|request| {
+            let etag = format!("\"{}\"", request.url().len());
+            if header(request, "If-None-Match").as_ref() == Some(&etag) {
+                return Response::from_string("").with_status_code(304);
+            }
+            Response::from_string(format!("page {}", request.url()))
+                .with_header(Header::from_bytes("ETag", etag).unwrap())
+        });
+        let dir = temp_dir("rust_parser_test_conditional_fetch");
+        let entries = entries(&url, &["LSLS10061U", "NDAB15009U"]);
+
+        let records = fetch_pages(&entries, &dir, &options()).unwrap();
+        assert_eq!(FetchSummary::new(&records).fetched, 2);
+        assert_eq!(records[1].page, "NDAB15009U.html");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("NDAB15009U.html")).unwrap(),
+            "page /course/NDAB15009U"
+        );
+        let state = FetchState::load(&dir).unwrap();
+        assert_eq!(
+            state.pages["LSLS10061U.html"].etag.as_deref(),
+            Some("\"18\"")
+        );
+
+        // nothing changed, so nothing is downloaded
+        let records = fetch_pages(&entries, &dir, &options()).unwrap();
+        assert_eq!(
+            FetchSummary::new(&records),
+            FetchSummary {
+                fetched: 0,
+                not_modified: 2,
+                failed: 0
+            }
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("LSLS10061U.html")).unwrap(),
+            "page /course/LSLS10061U"
+        );
+
+        let log = std::fs::read_to_string(dir.join(FETCH_LOG_FILE)).unwrap();
+        let logged: Vec<FetchRecord> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(logged.len(), 4);
+        assert_eq!(logged[3].outcome, FetchOutcome::NotModified);
+        // no temporary files are left behind
+        let files = std::fs::read_dir(&dir).unwrap().count();
+        assert_eq!(files, 4);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_retries() {
+        let requests = Arc::new(AtomicU32::new(0));
+        let counter = requests.clone();
+        let url = stub(move |request| {
+            if request.url().ends_with("NDAB15009U") {
+                return Response::from_string("gone").with_status_code(404);
+            }
+            if request.url().ends_with("NMAA04032U") {
+                return Response::from_string("down").with_status_code(500);
+            }
+            // the first two requests for the page fail
+            match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => Response::from_string("busy").with_status_code(503),
+                1 => Response::from_string("slow down")
+                    .with_status_code(429)
+                    .with_header(Header::from_bytes("Retry-After", "0").unwrap()),
+                _ => Response::from_string("page"),
+            }
+        });
+        let dir = temp_dir("rust_parser_test_retries");
+        let entries = entries(&url, &["LSLS10061U", "NDAB15009U", "NMAA04032U"]);
+        let options = FetchOptions {
+            concurrency: 1,
+            retries: 2,
+            ..options()
+        };
+
+        let records = fetch_pages(&entries, &dir, &options).unwrap();
+        assert_eq!(records[0].outcome, FetchOutcome::Fetched { status: 200 });
+        assert_eq!(records[0].attempts, 3);
+        // client errors are not retried
+        assert_eq!(
+            records[1].outcome,
+            FetchOutcome::Failed {
+                error: "HTTP 404".into()
+            }
+        );
+        assert_eq!(records[1].attempts, 1);
+        assert_eq!(records[2].attempts, 3);
+        assert!(!dir.join("NDAB15009U.html").exists());
+        assert!(!dir.join("NMAA04032U.html").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let url = stub(|_| Response::from_string("page"));
+        let dir = temp_dir("rust_parser_test_rate_limit");
+        let entries = entries(&url, &["A", "B", "C", "D", "E", "F"]);
+        let options = FetchOptions {
+            rate: 20.0,
+            concurrency: 4,
+            ..options()
+        };
+        let start = Instant::now();
+        let records = fetch_pages(&entries, &dir, &options).unwrap();
+        assert_eq!(FetchSummary::new(&records).fetched, 6);
+        // the first request is sent right away, the others 50ms apart
+        assert!(start.elapsed() >= Duration::from_millis(250));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
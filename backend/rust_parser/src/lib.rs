@@ -0,0 +1,23 @@
+//! Parser for the course pages of the KU course catalogue (kurser.ku.dk).
+//!
+//! The course model in [`parser`] is shared with the other backend services,
+//! which read the json written by the `rust_parser` binary back into it.
+//!
+//! ```no_run
+//! use rust_parser::parser::{parse_course, Course};
+//!
+//! let html = std::fs::read_to_string("NDAB15009U.html").unwrap();
+//! let parsed = parse_course(&html).unwrap();
+//! let json = serde_json::to_string(&parsed.course).unwrap();
+//! let course: Course = serde_json::from_str(&json).unwrap();
+//! assert_eq!(course.info.id.as_str(), "NDAB15009U");
+//! ```
+pub mod diff;
+pub mod fetch;
+pub mod manifest;
+pub mod parser;
+pub mod report;
+pub mod sink;
+pub mod sitemap;
+pub mod statistics;
+pub mod validation;
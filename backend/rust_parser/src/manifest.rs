@@ -0,0 +1,174 @@
+// Keeps track of what is in a json directory, so that only the pages that changed are parsed again
+use crate::parser::CourseCode;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// The manifest is hidden, so that it is not mistaken for a course by the services reading the directory
+pub const MANIFEST_FILE: &str = ".manifest.json";
+/// The course ids that changed in the last run, for the services that only want to update those
+pub const CHANGES_FILE: &str = ".changes.json";
+
+/// Pages parsed by another version of the parser are parsed again, bump it whenever a change to
+/// the parser changes its output. The snapshot test fails if the snapshots change without a bump
+pub const PARSER_VERSION: &str = "1.3.0";
+
+/// What was last parsed from every page in the html directory, keyed by the file name of the page
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub entries: BTreeMap<String, ManifestEntry>,
+    /// Hash of the ITX courses of the exam schedules the pages were parsed with, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub itx_hash: Option<String>,
+    /// Hash of the organisation mapping file the pages were parsed with, if it was not the embedded one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organisations_hash: Option<String>,
+    /// Whether the courses were written with the provenance of their fields
+    #[serde(default)]
+    pub provenance: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub source_hash: String,
+    pub parser_version: String,
+    // Both are missing if the page was expected not to give a course, e.g. it is cancelled
+    pub course_id: Option<CourseCode>,
+    pub output_hash: Option<String>,
+}
+
+/// The course ids that were added, changed or removed between two manifests
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Changes {
+    pub added: BTreeSet<CourseCode>,
+    pub changed: BTreeSet<CourseCode>,
+    pub removed: BTreeSet<CourseCode>,
+}
+
+/// Hex encoded sha256 of the bytes
+pub fn hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+impl Manifest {
+    /// Reads the manifest of a json directory, a directory without one has an empty manifest
+    pub fn load(json_dir: &Path) -> Result<Self> {
+        let path = json_dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let json = std::fs::read_to_string(&path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        serde_json::from_str(&json).with_context(|| format!("Invalid manifest {}", path.display()))
+    }
+
+    // The manifest is written to a temporary file first, so a crash never leaves half a manifest behind
+    pub fn save(&self, json_dir: &Path) -> Result<()> {
+        let path = json_dir.join(MANIFEST_FILE);
+        let tmp = json_dir.join(format!("{MANIFEST_FILE}.tmp"));
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, &path).with_context(|| format!("Could not write {}", path.display()))
+    }
+
+    /// The entry of a page if it was parsed from the same source by this version of the parser
+    pub fn current(&self, page: &str, source_hash: &str) -> Option<&ManifestEntry> {
+        self.entries.get(page).filter(|entry| {
+            entry.source_hash == source_hash && entry.parser_version == PARSER_VERSION
+        })
+    }
+
+    fn outputs(&self) -> BTreeMap<&CourseCode, &str> {
+        self.entries
+            .values()
+            .filter_map(|entry| Some((entry.course_id.as_ref()?, entry.output_hash.as_deref()?)))
+            .collect()
+    }
+
+    /// What changed for the courses of `previous` compared to this manifest
+    pub fn changes_since(&self, previous: &Manifest) -> Changes {
+        let (before, after) = (previous.outputs(), self.outputs());
+        let mut changes = Changes::default();
+        for (id, output_hash) in &after {
+            match before.get(id) {
+                None => changes.added.insert((*id).clone()),
+                Some(old) if old != output_hash => changes.changed.insert((*id).clone()),
+                Some(_) => false,
+            };
+        }
+        for id in before.keys().filter(|id| !after.contains_key(*id)) {
+            changes.removed.insert((*id).clone());
+        }
+        changes
+    }
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: Option<&str>, output: &str) -> ManifestEntry {
+        ManifestEntry {
+            source_hash: hash(output.as_bytes()),
+            parser_version: PARSER_VERSION.to_string(),
+            course_id: id.map(|id| id.parse().unwrap()),
+            output_hash: id.map(|_| hash(output.as_bytes())),
+        }
+    }
+
+    #[test]
+    fn test_changes_since() {
+        let mut previous = Manifest::default();
+        previous
+            .entries
+            .insert("A".into(), entry(Some("NDAB15009U"), "a"));
+        previous
+            .entries
+            .insert("B".into(), entry(Some("NDAB22007U"), "b"));
+        previous
+            .entries
+            .insert("C".into(), entry(Some("LSLS10061U"), "c"));
+
+        let mut current = previous.clone();
+        current
+            .entries
+            .insert("B".into(), entry(Some("NDAB22007U"), "b2"));
+        // the course was cancelled, so the page no longer gives a course
+        current.entries.insert("C".into(), entry(None, "c2"));
+        current
+            .entries
+            .insert("D".into(), entry(Some("LSLS10156U"), "d"));
+
+        let code = |id: &str| -> CourseCode { id.parse().unwrap() };
+        assert_eq!(
+            current.changes_since(&previous),
+            Changes {
+                added: BTreeSet::from([code("LSLS10156U")]),
+                changed: BTreeSet::from([code("NDAB22007U")]),
+                removed: BTreeSet::from([code("LSLS10061U")]),
+            }
+        );
+        assert!(current.changes_since(&current).is_empty());
+    }
+
+    #[test]
+    fn test_current() {
+        let mut manifest = Manifest::default();
+        manifest
+            .entries
+            .insert("A".into(), entry(Some("NDAB15009U"), "a"));
+        assert!(manifest.current("A", &hash(b"a")).is_some());
+        assert!(manifest.current("A", &hash(b"b")).is_none());
+        assert!(manifest.current("B", &hash(b"a")).is_none());
+
+        manifest.entries.get_mut("A").unwrap().parser_version = "0.0.0".into();
+        assert!(manifest.current("A", &hash(b"a")).is_none());
+    }
+}
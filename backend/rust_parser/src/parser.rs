@@ -1,51 +1,238 @@
-use anyhow::{bail, ensure, Context, Result};
-use serde::Serialize;
-use tl::VDom;
+use anyhow::{ensure, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
 
 use crate::parser::course_information::parse_course_info;
-pub mod course_information;
+mod course_information;
 
 use crate::parser::exam_information::parse_course_exams;
-pub mod exam_information;
+mod exam_information;
 
 use crate::parser::logistic_information::parse_logistic_info;
-pub mod logistic_information;
+mod logistic_information;
 
 use crate::parser::workload_information::parse_workloads;
-pub mod workload_information;
+mod workload_information;
 
 use crate::parser::content_serialiser::grab_htmls;
-pub mod content_serialiser;
+mod content_serialiser;
 
-#[derive(Debug, PartialEq, Serialize)]
+pub use crate::parser::course_code::CourseCode;
+pub mod course_code;
+
+use crate::parser::status_information::parse_course_status;
+mod status_information;
+
+use crate::parser::sections::Sections;
+mod sections;
+
+pub mod bilingual;
+
+pub mod error;
+pub use crate::parser::error::ParseError;
+
+pub mod exam_schedule;
+
+pub mod organisation;
+pub use crate::parser::organisation::OrganisationMapping;
+
+pub mod schema;
+pub use crate::parser::schema::{CourseRecord, SCHEMA_VERSION};
+
+#[cfg(test)]
+mod no_panic;
+#[cfg(test)]
+mod snapshot;
+
+// The saved course pages that the tests parse
+#[cfg(test)]
+pub(crate) const TEST_HTMLS_DIR: &str = "./test_data/pages";
+
+// The html of a saved page, e.g. `test_page("LSLS10061U")`
+#[cfg(test)]
+pub(crate) fn test_page(id: &str) -> String {
+    std::fs::read_to_string(format!("{TEST_HTMLS_DIR}/{id}.html")).unwrap()
+}
+
+// The course of a saved page that parses
+#[cfg(test)]
+pub(crate) fn test_course(id: &str) -> Course {
+    parse_course(&test_page(id)).unwrap().course
+}
+
+/// A course together with the non-fatal warnings collected whilst parsing it,
+/// and where every field came from if that was requested in the `ParseOptions`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ParsedCourse {
+    pub course: Course,
+    // Which language version of the course page this was parsed from
+    pub page_language: CourseLanguage,
+    pub warnings: Vec<ParseWarning>,
+    pub provenance: Option<Vec<Provenance>>,
+}
+impl ParsedCourse {
+    pub fn output(&self) -> CourseOutput<'_> {
+        CourseOutput {
+            schema_version: SCHEMA_VERSION,
+            course: &self.course,
+            provenance: self.provenance.as_ref(),
+        }
+    }
+}
+
+/// The course as it is written to disk, the provenance is only included if it was recorded
+#[derive(Debug, Serialize)]
+pub struct CourseOutput<'a> {
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub course: &'a Course,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<&'a Vec<Provenance>>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+    // Record the section, label and raw value that every field was parsed from
+    pub provenance: bool,
+    // The courses whose written exams are at ITX according to the exam schedules
+    pub itx_courses: BTreeSet<CourseCode>,
+    // The names of the departments and faculties, the embedded mapping is used if there is none
+    pub organisations: Option<Arc<OrganisationMapping>>,
+}
+
+/// Where in the page an output field came from, `section` is the id (or class if it has no id)
+/// of the element the value was found in, and `label` is the dt, h5 or heading text naming it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Provenance {
+    pub field: String,
+    pub section: String,
+    pub label: String,
+    pub raw: String,
+}
+
+/// Collects everything we learn about the parse besides the course itself,
+/// this is passed along to every section parser
+#[derive(Debug, Default)]
+pub(crate) struct Diagnostics {
+    pub warnings: Vec<ParseWarning>,
+    pub provenance: Option<Vec<Provenance>>,
+}
+impl Diagnostics {
+    pub fn new(options: &ParseOptions) -> Self {
+        Self {
+            warnings: Vec::new(),
+            provenance: options.provenance.then(Vec::new),
+        }
+    }
+
+    pub fn warn(&mut self, field: &str, heuristic: Heuristic, raw: &str) {
+        self.warnings.push(ParseWarning::new(field, heuristic, raw));
+    }
+
+    // Does nothing unless provenance was requested
+    pub fn trace(&mut self, field: &str, section: &str, label: &str, raw: &str) {
+        if let Some(provenance) = &mut self.provenance {
+            provenance.push(Provenance {
+                field: field.to_string(),
+                section: section.to_string(),
+                label: label.trim().to_string(),
+                raw: raw.to_string(),
+            });
+        }
+    }
+}
+
+/// Raised whenever a field could only be filled in by falling back on a heuristic,
+/// `raw` is the input the heuristic was applied to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ParseWarning {
+    pub field: String,
+    pub heuristic: Heuristic,
+    pub raw: String,
+}
+impl ParseWarning {
+    pub fn new(field: &str, heuristic: Heuristic, raw: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            heuristic,
+            raw: raw.to_string(),
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
+pub enum Heuristic {
+    // The duration was missing and was instead found in the schedule field
+    DurationFromSchedule,
+    // The ECTS field was not a number, so it was summed up from every "ECTS" in the page
+    EctsFromFullText,
+    // No schedule group was given, so it was guessed from the names of the weekdays
+    ScheduleFromWeekdays,
+    // No block numbers were given, so they were guessed from the season (spring, autumn, summer)
+    BlockFromSeason,
+    // The department is not in the organisation mapping, so it was kept as an unknown department
+    UnknownDepartment,
+    // The faculty is not in the organisation mapping, so it was kept as an unknown faculty
+    UnknownFaculty,
+    // A section of a course that can not be taken did not parse, so it was left empty
+    EmptySection,
+}
+
+/// A parsed course page, this is the record that is written to the json directory
+/// and read back by the other services
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Course {
     pub title: String,
+    // Danish pages also give the english title of the course
+    pub english_title: Option<String>,
+    #[serde(default)]
+    pub status: CourseStatus,
     pub info: CourseInformation,
     pub logistics: LogisticInformation,
     pub workloads: Vec<Workload>,
     pub exams: Vec<Exam>,
     pub description: Description,
+    // Label/value pairs of the course info and exam tables that we do not parse (yet)
+    #[serde(default)]
+    pub raw_fields: BTreeMap<String, String>,
+}
+
+// Whether students can take the course, only active courses should be shown as normal courses
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum CourseStatus {
+    // The json written before the status was parsed only has active courses
+    #[default]
+    Active,
+    Cancelled,
+    NotOffered,
+    ClosedForRegistration,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum CourseLanguage {
     English,
     Danish,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct CourseInformation {
-    pub id: String,
-    ects: f32,
-    block: Vec<Block>,
-    schedule: Vec<Schedule>,
-    language: Vec<Language>,
-    duration: Duration,
-    degree: Vec<Degree>,
-    capacity: Capacity,
+    pub id: CourseCode,
+    pub ects: f32,
+    // The blocks the course is taught in, a course spanning two blocks has both of them
+    pub block: Vec<Block>,
+    pub schedule: Vec<Schedule>,
+    // The languages the course is taught in
+    pub language: Vec<Language>,
+    pub duration: Duration,
+    pub degree: Vec<Degree>,
+    pub capacity: Capacity,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum Department {
     // Faculty of Science
     PlantAndEnvironmentalScience,
@@ -68,89 +255,75 @@ pub enum Department {
     CellularAndMolecularMedicine,
     Pharmacy,
     Globe,
+    // A department that is not in the organisation mapping (yet), with its name as on the page
+    Unknown(String),
 }
-impl Department {
-    fn from_str(s: &str) -> Result<Self> {
-        match s.replace('\n', " ").as_str() {
-            "Department of Computer Science" | "Datalogisk Institut" => {
-                Ok(Department::ComputerScience)
-            }
-            "Institut for Idræt og Ernæring" | "Department of Nutrition, Exercise and Sports" => {
-                Ok(Department::NutritionExerciseAndSports)
-            }
-            "Statens Naturhistoriske Museum" | "The Natural History Museum of Denmark" => {
-                Ok(Department::NaturalHistoryMuseumOfDenmark)
-            }
-            "Institut for Plante- og Miljøvidenskab"
-            | "Department of Plant and Environmental Sciences" => {
-                Ok(Department::PlantAndEnvironmentalScience)
-            }
-            "Institut for Matematiske Fag" | "Department of Mathematical Sciences" => {
-                Ok(Department::Mathematics)
-            }
-            "Niels Bohr Institutet" | "The Niels Bohr Institute" => {
-                Ok(Department::NielsBohrInstitute)
-            }
-            "Institut for Geovidenskab og Naturforvaltning"
-            | "Department of Geoscience and Natural Resource Management" => {
-                Ok(Department::GeosciencesAndNaturalResourceManagement)
-            }
-            "Institut for Naturfagenes Didaktik" | "Department of Science Education" => {
-                Ok(Department::ScienceEducation)
-            }
-            "Institut for Fødevare- og Ressourceøkonomi"
-            | "Department of Food and Resource Economics" => {
-                Ok(Department::FoodAndResourceEconomics)
-            }
-            "Institut for Fødevarevidenskab" | "Department of Food Science" => {
-                Ok(Department::FoodScience)
-            }
-            "Kemisk Institut" | "Department of Chemistry" => Ok(Department::Chemistry),
-            "Biologisk Institut" | "Department of Biology" => Ok(Department::Biology),
-            "Department of Veterinary and Animal Sciences"
-            | "Institut for Veterinær- og Husdyrvidenskab (IVH)" => {
-                Ok(Department::VeterinaryAndAnimalSciences)
-            }
-            "Department of Biomedical Sciences" => Ok(Department::BiomedicalSciences),
-            "Department of Pharmacy" => Ok(Department::Pharmacy),
-            "Institut for Lægemiddeldesign og Farmakologi"
-            | "Department of Drug Design and Pharmacology" => {
-                Ok(Department::DrugDesignAndPharmacology)
-            }
-            "Department of Cellular and Molecular Medicine" => {
-                Ok(Department::CellularAndMolecularMedicine)
-            }
-            "Department of Public Health" => Ok(Department::PublicHealth),
-            "Institut for Nordiske Studier og Sprogvidenskab" => {
-                bail!("Nordic studies not supported <EXPECTED>")
-            }
-            "GLOBE Institute" => Ok(Department::Globe),
-            _ => bail!("Unknown department: {}", s),
-        }
-    }
-}
-
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum Faculty {
     Science,
+    // A faculty that is not in the organisation mapping (yet), with its name as on the page
+    Unknown(String),
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct Coordinator {
-    name: String,
-    email: String,
+    // The name as it is written on the page
+    pub name: String,
+    // The name split into given and family names, empty in json written before they were split
+    #[serde(default)]
+    pub given_name: String,
+    #[serde(default)]
+    pub family_name: String,
+    // Lowercased, the same person is written with different cases on different pages
+    pub email: String,
+    #[serde(default)]
+    pub email_kind: Option<EmailKind>,
 }
 impl Coordinator {
+    /// A coordinator with the email normalised and the name split into given and family names
     pub fn new(name: String, email: String) -> Self {
-        Self { name, email }
+        let (given_name, family_name) = logistic_information::split_name(&name);
+        let email = logistic_information::normalise_email(&email);
+        let email_kind = Some(EmailKind::of(&email));
+        Self {
+            name,
+            given_name,
+            family_name,
+            email,
+            email_kind,
+        }
+    }
+}
+
+// Whose address a coordinator email is, the alumni addresses are the student accounts
+// that some teaching assistants and PhD students are listed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum EmailKind {
+    Staff,
+    Alumni,
+    External,
+}
+impl EmailKind {
+    pub fn of(email: &str) -> Self {
+        let domain = email
+            .rsplit_once('@')
+            .map(|(_, domain)| domain)
+            .unwrap_or_default();
+        if domain == "alumni.ku.dk" {
+            EmailKind::Alumni
+        } else if domain == "ku.dk" || domain.ends_with(".ku.dk") {
+            EmailKind::Staff
+        } else {
+            EmailKind::External
+        }
     }
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct LogisticInformation {
-    departments: Vec<Department>,
-    faculty: Faculty,
-    coordinators: Vec<Coordinator>,
+    pub departments: Vec<Department>,
+    pub faculty: Faculty,
+    pub coordinators: Vec<Coordinator>,
 }
 impl LogisticInformation {
     pub fn new(
@@ -169,7 +342,7 @@ impl LogisticInformation {
 impl CourseInformation {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
-        id: String,
+        id: CourseCode,
         ects: f32,
         block: Vec<Block>,
         schedule: Vec<Schedule>,
@@ -191,7 +364,7 @@ impl CourseInformation {
     }
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum Block {
     One,
     Two,
@@ -201,7 +374,7 @@ pub enum Block {
     Other(String),
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum Schedule {
     A,
     B,
@@ -211,13 +384,13 @@ pub enum Schedule {
     Other(String),
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum Language {
     Danish,
     English,
 }
 
-#[derive(Debug, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum Duration {
     One,
     Two,
@@ -225,7 +398,7 @@ pub enum Duration {
     Custom(String),
 }
 
-#[derive(Debug, PartialEq, Eq, Ord, PartialOrd, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
 pub enum Degree {
     Phd,
     Bachelor,
@@ -233,19 +406,22 @@ pub enum Degree {
     Propædeutik,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct Capacity(pub Option<u32>);
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum Exam {
     Oral(Option<u32>),
     Written(Option<u32>),
+    // A written exam held digitally on-site at ITX, only known from the exam schedules
+    #[serde(rename = "ITX")]
+    Itx(Option<u32>),
     Assignment(Option<u32>),
     ContinuousAssessment,
     Other,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum WorkloadType {
     Exam,
     ELearning,
@@ -286,15 +462,15 @@ impl WorkloadType {
             "Praktik" | "Practical Training" => Ok(WorkloadType::PracticalTraining),
 
             "Guidance" | "Vejledning" => Ok(WorkloadType::Guidance),
-            _ => bail!("Unknown workload type: {}", s),
+            _ => Err(ParseError::unknown("workloads", s).into()),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Workload {
-    workload_type: WorkloadType,
-    hours: f32,
+    pub workload_type: WorkloadType,
+    pub hours: f32,
 }
 impl Workload {
     pub fn new(workload_type: WorkloadType, hours: f32) -> Self {
@@ -305,7 +481,7 @@ impl Workload {
     }
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct Description {
     pub content: String,
     pub learning_outcome: String,
@@ -313,55 +489,125 @@ pub struct Description {
     pub summary: String,
 }
 
-pub fn parse_course(html: &str) -> Result<Course> {
+pub fn parse_course(html: &str) -> Result<ParsedCourse> {
+    parse_course_with_options(html, &ParseOptions::default())
+}
+
+pub fn parse_course_with_options(html: &str, options: &ParseOptions) -> Result<ParsedCourse> {
     let dom = tl::parse(html, tl::ParserOptions::default())?;
-    let content = dom.get_element_by_id("content");
-    let title = parse_title(&dom)?;
+    // every section is located once, the field parsers look them up in `page`
+    let page = Sections::new(&dom, html);
+    let mut diagnostics = Diagnostics::new(options);
+    let title = parse_title(&page, &mut diagnostics)?;
+    let english_title = parse_english_title(&page, &mut diagnostics);
+    let page_language = parse_page_language(&page);
+    let status = parse_course_status(&page, &mut diagnostics);
 
     ensure!(
-        content.is_some(),
+        page.by_id("content").is_some(),
         "Unable to find content element, this should not happen"
     );
-    let info = parse_course_info(&dom).context(format!("Unable to parse course: {title}"))?;
-    let logistic_info = parse_logistic_info(&dom).context(format!(
-        "Unable to parse logistic information for course: {title}"
-    ))?;
-
-    let exam_info = parse_course_exams(&dom).context(format!(
+    let mut raw_fields = BTreeMap::new();
+    let info = parse_course_info(&page, &mut diagnostics, &mut raw_fields)
+        .context(format!("Unable to parse course: {title}"))?;
+    let organisations = options
+        .organisations
+        .as_deref()
+        .unwrap_or_else(|| OrganisationMapping::embedded());
+    let logistic_info = parse_logistic_info(&page, &mut diagnostics, organisations).context(
+        format!("Unable to parse logistic information for course: {title}"),
+    )?;
+
+    let exam_info = parse_course_exams(&page, &mut diagnostics, &mut raw_fields).context(format!(
         "Unable to parse exam information for course: {title}"
-    ))?;
+    ));
+    let exam_info = section_or_empty(&status, "exams", exam_info, &mut diagnostics)?;
 
-    let workload_info = parse_workloads(&dom).context(format!(
+    let workload_info = parse_workloads(&page, &mut diagnostics).context(format!(
         "Unable to parse workload information for course: {title}"
-    ))?;
+    ));
+    let workload_info = section_or_empty(&status, "workloads", workload_info, &mut diagnostics)?;
 
-    let html_info = grab_htmls(&dom).context(format!(
+    let html_info = grab_htmls(&page, &mut diagnostics).context(format!(
         "Unable to grab html information for course: {title}"
-    ))?;
+    ));
+    let html_info = section_or_empty(&status, "description", html_info, &mut diagnostics)?;
 
-    Ok(Course {
+    let mut course = Course {
         title,
+        english_title,
+        status,
         info,
         logistics: logistic_info,
         exams: exam_info,
         workloads: workload_info,
         description: html_info,
+        raw_fields,
+    };
+    exam_schedule::mark_itx(&mut course, &options.itx_courses);
+
+    Ok(ParsedCourse {
+        course,
+        page_language,
+        warnings: diagnostics.warnings,
+        provenance: diagnostics.provenance,
     })
 }
-fn parse_title(dom: &VDom) -> Result<String> {
-    let title = dom
-        .get_elements_by_class_name("courseTitle")
-        .next()
+// Courses that can not be taken are often missing their exam, workload and description sections,
+// they are still written with the sections left empty so the catalogue can mark them.
+// The course information and logistics are needed for every course
+fn section_or_empty<T: Default>(
+    status: &CourseStatus,
+    field: &str,
+    section: Result<T>,
+    diagnostics: &mut Diagnostics,
+) -> Result<T> {
+    match section {
+        Err(e) if *status != CourseStatus::Active => {
+            diagnostics.warn(field, Heuristic::EmptySection, &format!("{e:#}"));
+            Ok(T::default())
+        }
+        section => section,
+    }
+}
+
+// Only the danish pages have the "Engelsk titel" section
+fn parse_english_title(page: &Sections, diagnostics: &mut Diagnostics) -> Option<String> {
+    let parser = page.parser;
+    let label = page.by_id("accordion-englisttitle")?;
+    let section = page.by_id("course-language")?;
+    let raw_title = section.inner_text(parser);
+    diagnostics.trace(
+        "english_title",
+        "course-language",
+        &label.inner_text(parser),
+        &raw_title,
+    );
+    let english_title = raw_title.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!english_title.is_empty()).then_some(english_title)
+}
+
+// The html lang attribute is always "da", so instead we look at the heading of the course content
+fn parse_page_language(page: &Sections) -> CourseLanguage {
+    let heading = page
+        .by_id("accordion-coursecontent")
+        .map(|tag| tag.inner_text(page.parser));
+    match heading.as_deref() {
+        Some("Content") => CourseLanguage::English,
+        _ => CourseLanguage::Danish,
+    }
+}
+
+fn parse_title(page: &Sections, diagnostics: &mut Diagnostics) -> Result<String> {
+    let title = page
+        .by_class("courseTitle")
+        .first()
         .context("Unable to find course title")
-        .and_then(|elem| {
-            elem.get(dom.parser())
-                .context("Unable to grab parser for the dom, this should not happen")
-                .map(|tag| tag.inner_text(dom.parser()))
-        });
-
-    let binding = title
-        .unwrap_or_else(|_| "Error unwrapping html title".into())
-        .replace(['\u{a0}', '\n'], " ");
+        .map(|tag| tag.inner_text(page.parser));
+
+    let raw_title = title.unwrap_or_else(|_| "Error unwrapping html title".into());
+    diagnostics.trace("title", "courseTitle", "h1", &raw_title);
+    let binding = raw_title.replace(['\u{a0}', '\n'], " ");
 
     // Then split them
     let res: Vec<&str> = binding.split_whitespace().collect();
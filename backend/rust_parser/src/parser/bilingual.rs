@@ -0,0 +1,296 @@
+// Merging the danish and english page of a course into a single record
+use crate::parser::{
+    Course, CourseInformation, CourseLanguage, CourseStatus, Description, Exam,
+    LogisticInformation, ParsedCourse, Workload,
+};
+use anyhow::{bail, ensure, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A value that is written differently on the danish and the english page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Localised<T> {
+    pub da: Option<T>,
+    pub en: Option<T>,
+}
+
+/// A course built from both language versions of its page, the language-neutral fields
+/// are taken from the danish page if the two versions disagree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BilingualCourse {
+    pub title: Localised<String>,
+    pub status: CourseStatus,
+    pub info: CourseInformation,
+    pub logistics: LogisticInformation,
+    pub workloads: Vec<Workload>,
+    pub exams: Vec<Exam>,
+    pub description: Localised<Description>,
+    pub raw_fields: Localised<BTreeMap<String, String>>,
+}
+
+/// A language-neutral field that differs between the danish and the english page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Mismatch {
+    pub field: String,
+    pub danish: serde_json::Value,
+    pub english: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct MergedCourse {
+    pub course: BilingualCourse,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl BilingualCourse {
+    // Used when only one of the language versions exists
+    pub fn from_single(course: Course, language: &CourseLanguage) -> Self {
+        let (title, description, raw_fields) = match language {
+            CourseLanguage::Danish => (
+                Localised {
+                    da: Some(course.title),
+                    en: course.english_title,
+                },
+                Localised {
+                    da: Some(course.description),
+                    en: None,
+                },
+                Localised {
+                    da: Some(course.raw_fields),
+                    en: None,
+                },
+            ),
+            CourseLanguage::English => (
+                Localised {
+                    da: None,
+                    en: Some(course.title),
+                },
+                Localised {
+                    da: None,
+                    en: Some(course.description),
+                },
+                Localised {
+                    da: None,
+                    en: Some(course.raw_fields),
+                },
+            ),
+        };
+        BilingualCourse {
+            title,
+            status: course.status,
+            info: course.info,
+            logistics: course.logistics,
+            workloads: course.workloads,
+            exams: course.exams,
+            description,
+            raw_fields,
+        }
+    }
+}
+
+/// Merges the two language versions of a page, they can be given in any order
+pub fn merge_pages(first: ParsedCourse, second: ParsedCourse) -> Result<MergedCourse> {
+    match (&first.page_language, &second.page_language) {
+        (CourseLanguage::Danish, CourseLanguage::English) => {
+            merge_courses(first.course, second.course)
+        }
+        (CourseLanguage::English, CourseLanguage::Danish) => {
+            merge_courses(second.course, first.course)
+        }
+        (language, _) => bail!(
+            "Both pages of {} are in {:?}, expected a danish and an english page",
+            first.course.info.id,
+            language
+        ),
+    }
+}
+
+pub fn merge_courses(danish: Course, english: Course) -> Result<MergedCourse> {
+    ensure!(
+        danish.info.id == english.info.id,
+        "Cannot merge pages of two different courses: {} and {}",
+        danish.info.id,
+        english.info.id
+    );
+
+    let mut mismatches = Vec::new();
+    compare("status", &danish.status, &english.status, &mut mismatches);
+    let (da, en) = (&danish.info, &english.info);
+    compare("info.ects", &da.ects, &en.ects, &mut mismatches);
+    compare("info.block", &da.block, &en.block, &mut mismatches);
+    compare("info.schedule", &da.schedule, &en.schedule, &mut mismatches);
+    compare("info.language", &da.language, &en.language, &mut mismatches);
+    compare("info.duration", &da.duration, &en.duration, &mut mismatches);
+    compare("info.degree", &da.degree, &en.degree, &mut mismatches);
+    compare("info.capacity", &da.capacity, &en.capacity, &mut mismatches);
+    let (da, en) = (&danish.logistics, &english.logistics);
+    compare(
+        "logistics.departments",
+        &da.departments,
+        &en.departments,
+        &mut mismatches,
+    );
+    compare(
+        "logistics.faculty",
+        &da.faculty,
+        &en.faculty,
+        &mut mismatches,
+    );
+    compare(
+        "logistics.coordinators",
+        &da.coordinators,
+        &en.coordinators,
+        &mut mismatches,
+    );
+    compare(
+        "workloads",
+        &danish.workloads,
+        &english.workloads,
+        &mut mismatches,
+    );
+    compare("exams", &danish.exams, &english.exams, &mut mismatches);
+
+    let course = BilingualCourse {
+        title: Localised {
+            da: Some(danish.title),
+            en: Some(english.title),
+        },
+        status: danish.status,
+        info: danish.info,
+        logistics: danish.logistics,
+        workloads: danish.workloads,
+        exams: danish.exams,
+        description: Localised {
+            da: Some(danish.description),
+            en: Some(english.description),
+        },
+        raw_fields: Localised {
+            da: Some(danish.raw_fields),
+            en: Some(english.raw_fields),
+        },
+    };
+    Ok(MergedCourse { course, mismatches })
+}
+
+fn compare<T: PartialEq + Serialize>(
+    field: &str,
+    danish: &T,
+    english: &T,
+    mismatches: &mut Vec<Mismatch>,
+) {
+    if danish != english {
+        mismatches.push(Mismatch {
+            field: field.to_string(),
+            danish: serde_json::to_value(danish).unwrap_or_default(),
+            english: serde_json::to_value(english).unwrap_or_default(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_course, test_page, Capacity};
+
+    fn parse(id: &str) -> ParsedCourse {
+        parse_course(&test_page(id)).unwrap()
+    }
+
+    #[test]
+    fn test_merge_reports_mismatches() {
+        let danish = parse("LSLS10061U");
+        // we have no english version of this page, so we pretend and change some fields
+        let mut english = parse("LSLS10061U");
+        english.page_language = CourseLanguage::English;
+        english.course.title = "International Natural Resource Management".to_string();
+        english.course.info.capacity = Capacity(Some(60));
+
+        let merged = merge_pages(english, danish).unwrap();
+        assert_eq!(
+            merged.mismatches,
+            vec![Mismatch {
+                field: "info.capacity".to_string(),
+                danish: serde_json::json!(70),
+                english: serde_json::json!(60),
+            }]
+        );
+        assert_eq!(merged.course.info.capacity, Capacity(Some(70)));
+        assert_eq!(
+            merged.course.title,
+            Localised {
+                da: Some("International Naturforvaltning".to_string()),
+                en: Some("International Natural Resource Management".to_string()),
+            }
+        );
+    }
+
+    // The pages of the courses that are taught in english are saved from the english catalogue,
+    // NFYK13004U has the english labels, a "Content" heading and no english title.
+    // We have no danish version of it, so that is made from the english page
+    #[test]
+    fn test_merge_english_page() {
+        let english_html = test_page("NFYK13004U");
+        let english = parse_course(&english_html).unwrap();
+        assert_eq!(english.page_language, CourseLanguage::English);
+        let danish_html = english_html
+            .replace(
+                r#"id="accordion-coursecontent">Content<"#,
+                r#"id="accordion-coursecontent">Kursusindhold<"#,
+            )
+            .replace("Quantum Field Theory 1</h1>", "Kvantefeltteori 1</h1>");
+        let danish = parse_course(&danish_html).unwrap();
+        assert_eq!(danish.page_language, CourseLanguage::Danish);
+
+        let merged = merge_pages(english.clone(), danish).unwrap();
+        assert_eq!(merged.mismatches, vec![]);
+        assert_eq!(
+            merged.course.title,
+            Localised {
+                da: Some("Kvantefeltteori 1".to_string()),
+                en: Some("Quantum Field Theory 1".to_string()),
+            }
+        );
+        assert_eq!(merged.course.info, english.course.info);
+        assert_eq!(
+            merged.course.description.en.as_ref(),
+            Some(&english.course.description)
+        );
+        let raw_fields = merged.course.raw_fields.en.unwrap();
+        assert_eq!(raw_fields["Aid"], "Without aids");
+
+        // the english labels are read like the danish ones, so a difference is a mismatch
+        let limited = danish_html.replace(
+            "<dt>Course capacity</dt><dd>No restriction",
+            "<dt>Kursuskapacitet</dt><dd>40",
+        );
+        let merged = merge_pages(parse_course(&limited).unwrap(), english).unwrap();
+        assert_eq!(
+            merged.mismatches,
+            vec![Mismatch {
+                field: "info.capacity".to_string(),
+                danish: serde_json::json!(40),
+                english: serde_json::Value::Null,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_merge_different_courses() {
+        let danish = parse("NDAB15009U");
+        let english = parse("NDAB22007U");
+        assert!(merge_pages(danish, english).is_err());
+        assert!(merge_pages(parse("LSLS10061U"), parse("LSLS10061U")).is_err());
+    }
+
+    #[test]
+    fn test_single_danish_page_keeps_english_title() {
+        let parsed = parse("LSLS10061U");
+        let course = BilingualCourse::from_single(parsed.course, &parsed.page_language);
+        assert_eq!(
+            course.title.en.as_deref(),
+            Some("International Natural Resource Management")
+        );
+        assert!(course.description.en.is_none());
+    }
+}
@@ -1,40 +1,60 @@
-use crate::parser::Description;
+use crate::parser::{Description, Diagnostics, ParseError, Sections};
 
-use tl::VDom;
-
-use anyhow::{Context, Result};
+use anyhow::Result;
 
 // grab some specific htmls and return the html
-pub fn grab_htmls(dom: &VDom) -> Result<Description> {
-    let parser = dom.parser();
-    let content_html = dom
-        .get_element_by_id("course-content")
-        .context("Unable to find course content")?
-        .get(parser)
-        .context("Unable to grab parser for the dom, this should not happen")?;
-
-    let learning_outcome_html = dom
-        .get_element_by_id("course-description")
-        .context("Unable to find learning outcomes")?
-        .get(parser)
-        .context("Unable to grab parser for the dom, this should not happen")?
+pub fn grab_htmls(page: &Sections, diagnostics: &mut Diagnostics) -> Result<Description> {
+    let parser = page.parser;
+    let content_html = page.by_id("course-content").ok_or(ParseError::Missing {
+        field: "description.content",
+    })?;
+
+    let learning_outcome_html = page
+        .by_id("course-description")
+        .ok_or(ParseError::Missing {
+            field: "description.learning_outcome",
+        })?
         .inner_html(parser);
 
     // Handle that recommended qualifications might be none
-    let recommended_qualifications_html = dom
-        .get_element_by_id("course-skills") // this might be none
-        .and_then(|elem| {
-            Some(
-                elem.get(parser)
-                    .context("Unable to grab parser for the dom, this should not happen")
-                    .ok()?
-                    .inner_html(parser),
-            )
-        });
+    let recommended_qualifications_html = page
+        .by_id("course-skills") // this might be none
+        .map(|tag| tag.inner_html(parser));
 
     let recommended_qualifications_html =
         recommended_qualifications_html.filter(|s| !(s.contains("Ingen") || s.contains("None")));
 
+    if diagnostics.provenance.is_some() {
+        let label = |id: &str| section_label(page, id).unwrap_or_default();
+        diagnostics.trace(
+            "description.content",
+            "course-content",
+            &label("course-content"),
+            &content_html.inner_html(parser),
+        );
+        // the summary is cut from the text of the content
+        diagnostics.trace(
+            "description.summary",
+            "course-content",
+            &label("course-content"),
+            &content_html.inner_text(parser),
+        );
+        diagnostics.trace(
+            "description.learning_outcome",
+            "course-description",
+            &label("course-description"),
+            &learning_outcome_html,
+        );
+        if let Some(recommended_qualifications) = &recommended_qualifications_html {
+            diagnostics.trace(
+                "description.recommended_qualifications",
+                "course-skills",
+                &label("course-skills"),
+                recommended_qualifications,
+            );
+        }
+    }
+
     // grab the first 300 chars of the content
     let summary = content_html
         .inner_text(parser)
@@ -49,3 +69,11 @@ pub fn grab_htmls(dom: &VDom) -> Result<Description> {
         summary,
     })
 }
+
+// The heading of a section is the accordion link that the section is labelled by
+fn section_label(page: &Sections, id: &str) -> Option<String> {
+    let section = page.by_id(id)?;
+    let label_id = section.attributes().get("aria-labelledby")??.as_utf8_str();
+    let label = page.by_id(label_id.as_ref())?;
+    Some(label.inner_text(page.parser).to_string())
+}
@@ -0,0 +1,198 @@
+use anyhow::{bail, ensure, Context, Result};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject, StringValidation};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A KU course code such as `NDAB15009U`, which is made up of
+/// - a faculty letter (`N`)
+/// - the department letters (`DAB`)
+/// - the level/number digits (`15009`)
+/// - a suffix that is either `U`, `E` or `P` (`U`)
+///
+/// The code is stored as it was written, it is serialised as a plain string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct CourseCode {
+    code: String,
+    // byte offsets of where the department letters and the digits end
+    department_end: usize,
+    number_end: usize,
+}
+
+impl CourseCode {
+    pub fn as_str(&self) -> &str {
+        &self.code
+    }
+
+    pub fn faculty_prefix(&self) -> char {
+        // the constructor guarantees that there is at least a faculty letter
+        self.code.chars().next().unwrap_or_default()
+    }
+
+    pub fn department(&self) -> &str {
+        let faculty_len = self.faculty_prefix().len_utf8();
+        &self.code[faculty_len..self.department_end]
+    }
+
+    pub fn number(&self) -> &str {
+        &self.code[self.department_end..self.number_end]
+    }
+
+    pub fn suffix(&self) -> char {
+        self.code[self.number_end..]
+            .chars()
+            .next()
+            .unwrap_or_default()
+    }
+}
+
+// The letters of the codes, the same as the pattern of the schema
+fn is_code_letter(c: char) -> bool {
+    c.is_ascii_uppercase() || matches!(c, 'Æ' | 'Ø' | 'Å')
+}
+
+impl FromStr for CourseCode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let code = s.trim();
+        let mut chars = code.char_indices().peekable();
+
+        let (_, faculty) = chars.next().context("Course code is empty")?;
+        ensure!(
+            is_code_letter(faculty),
+            "Course code {code} does not start with a faculty letter"
+        );
+
+        let mut department_end = faculty.len_utf8();
+        while let Some((i, c)) = chars.next_if(|(_, c)| is_code_letter(*c)) {
+            department_end = i + c.len_utf8();
+        }
+        ensure!(
+            department_end > faculty.len_utf8(),
+            "Course code {code} has no department letters"
+        );
+
+        let mut number_end = department_end;
+        while let Some((i, c)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+            number_end = i + c.len_utf8();
+        }
+        ensure!(
+            number_end > department_end,
+            "Course code {code} has no level/number digits"
+        );
+
+        match chars.next() {
+            Some((_, 'U' | 'E' | 'P')) => {}
+            Some((_, c)) => bail!("Course code {code} has an unknown suffix: {c}"),
+            None => bail!("Course code {code} is missing its suffix"),
+        }
+        ensure!(
+            chars.next().is_none(),
+            "Course code {code} has trailing characters after the suffix"
+        );
+
+        Ok(Self {
+            code: code.to_string(),
+            department_end,
+            number_end,
+        })
+    }
+}
+
+impl TryFrom<String> for CourseCode {
+    type Error = anyhow::Error;
+
+    // the pages are trimmed when they are parsed, a serialised code never has whitespace around it
+    fn try_from(value: String) -> Result<Self> {
+        ensure!(
+            value.trim() == value,
+            "Course code {value:?} has whitespace around it"
+        );
+        value.parse()
+    }
+}
+
+impl From<CourseCode> for String {
+    fn from(value: CourseCode) -> Self {
+        value.code
+    }
+}
+
+// The schema is a string, the pattern allows the same letters as `FromStr`
+impl JsonSchema for CourseCode {
+    fn schema_name() -> String {
+        "CourseCode".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some("^[A-ZÆØÅ]{2,}[0-9]+[UEP]$".to_string()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+impl fmt::Display for CourseCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segments() {
+        let code: CourseCode = "NDAB15009U".parse().unwrap();
+        assert_eq!(code.faculty_prefix(), 'N');
+        assert_eq!(code.department(), "DAB");
+        assert_eq!(code.number(), "15009");
+        assert_eq!(code.suffix(), 'U');
+        assert_eq!(code.to_string(), "NDAB15009U");
+
+        let code: CourseCode = "HØEB01001E".parse().unwrap();
+        assert_eq!(code.faculty_prefix(), 'H');
+        assert_eq!(code.department(), "ØEB");
+        assert_eq!(code.suffix(), 'E');
+    }
+
+    #[test]
+    fn test_invalid_codes() {
+        for code in [
+            "",
+            "NORS",
+            "N15009U",
+            "NDAB15009",
+            "NDAB15009X",
+            "NDABU",
+            "ndab15009u",
+            "ÜDAB15009U",
+            "NΔAB15009U",
+        ] {
+            assert!(
+                code.parse::<CourseCode>().is_err(),
+                "{code} should not parse"
+            );
+        }
+    }
+
+    #[test]
+    fn test_serialises_as_string() {
+        let code: CourseCode = "LSLS10061U".parse().unwrap();
+        assert_eq!(serde_json::to_string(&code).unwrap(), "\"LSLS10061U\"");
+        let parsed: CourseCode = serde_json::from_str("\"LSLS10061U\"").unwrap();
+        assert_eq!(parsed, code);
+        assert!(serde_json::from_str::<CourseCode>("\"LSLS\"").is_err());
+        assert!(serde_json::from_str::<CourseCode>("\" LSLS10061U\"").is_err());
+    }
+}
@@ -1,48 +1,38 @@
 // File for the course info side-table
 use crate::parser;
-use crate::parser::CourseInformation;
+use crate::parser::{CourseCode, CourseInformation, Diagnostics, Heuristic, ParseError, Sections};
 use anyhow::{bail, ensure, Context, Result};
-use tl::VDom;
-
-pub fn parse_course_info(dom: &VDom) -> Result<CourseInformation> {
-    let parser = dom.parser();
-    let panel_bodies = dom.get_elements_by_class_name("panel-body");
-    // there might be multiple panel-bodies, so we need to check each one
-    // for the dl element (only the course info should have a dl element)
-    for panel_body in panel_bodies {
-        let mut dl_elements = panel_body
-            .get(parser)
-            .context("Failed to get panel-body")?
-            .as_tag()
-            .context("Failed to get panel-body as tag")?
-            .query_selector(parser, "dl")
-            .context("Failed to get dl from panel-body")?;
-        match dl_elements.next() {
-            Some(handle) => {
-                let node = handle
-                    .get(parser)
-                    .context("Failed to get node")?
-                    .as_tag()
-                    .context("Failed to get node as tag")?;
-                // parse DL
-                let course_infos = parse_dl(node, parser)?;
-                //println!("{course_infos:?}");
-                // parse the course information
-                let coerced_course_info = coerce_course_info(&course_infos, dom);
-                return coerced_course_info;
-            }
-            None => continue,
+use std::collections::BTreeMap;
+
+// Every row of the dl that is not consumed here ends up in `raw_fields`
+pub fn parse_course_info(
+    page: &Sections,
+    diagnostics: &mut Diagnostics,
+    raw_fields: &mut BTreeMap<String, String>,
+) -> Result<CourseInformation> {
+    // there might be multiple panel-bodies, only the course info should have a dl element
+    let dl = page
+        .info_dl
+        .context("No dl element found in the panel-body")?;
+    let course_infos = parse_dl(dl, page.parser)?;
+    for (key, value) in &course_infos {
+        if info_field(key).is_none() {
+            raw_fields
+                .entry(key.trim().to_string())
+                .or_insert_with(|| value.trim().to_string());
         }
     }
-    bail!("No dl element found in the panel-body");
+    // parse the course information
+    coerce_course_info(&course_infos, page, diagnostics)
 }
 
 fn coerce_course_info(
     course_info: &[(String, String)],
-    dom: &VDom,
+    page: &Sections,
+    diagnostics: &mut Diagnostics,
 ) -> Result<parser::CourseInformation> {
     // dbg!(&course_info);
-    let mut id: Option<String> = None;
+    let mut id: Option<CourseCode> = None;
     let mut ects: Option<f32> = None;
     let mut block: Option<Vec<parser::Block>> = None;
     let mut schedule: Option<Vec<parser::Schedule>> = None;
@@ -59,53 +49,74 @@ fn coerce_course_info(
         .context("Failed to find course code (SHOULD BE IMPOSSIBLE)")?;
     parse_code(&course_code.1)?;
 
+    for (key, value) in course_info {
+        if let Some(field) = info_field(key) {
+            diagnostics.trace(field, "panel-body", key, value);
+        }
+    }
+
     for (key, value) in course_info {
         match key.as_str() {
             "Course code" | "Kursuskode" => id = Some(parse_code(value)?), // "Kursuskode" is the danish version of "Course code
             "Language" | "Sprog" => language = Some(parse_language(value)?),
-            "Point" | "Credit" => ects = Some(parse_ects(value, dom)?), // "Point" is the danish version of "Credit"
+            "Point" | "Credit" => ects = Some(parse_ects(value, page, diagnostics)?), // "Point" is the danish version of "Credit"
             "Level" | "Niveau" => degree = Some(parse_degree(value)?),
             "Duration" | "Varighed" => duration = Some(parse_duration(value)?),
-            "Schedule" | "Skemagruppe" => schedule = Some(parse_schedule(value)?),
+            "Schedule" | "Skemagruppe" => schedule = Some(parse_schedule(value, diagnostics)?),
             "Course capacity" | "Kursuskapacitet" => capacity = parse_capacity(value),
             _ => continue,
         }
     }
 
     // print every error with the contents of the course_info
-    let id = id.context("Failed to get id")?;
-    let ects = ects.context("Failed to get ECTS")?;
-    let schedule = schedule.context("Failed to find any schedules")?;
-    let language = language.context("Failed to get language")?;
-    let duration = duration.map_or_else(
+    let id = id.ok_or(ParseError::Missing { field: "info.id" })?;
+    let ects = ects.ok_or(ParseError::Missing { field: "info.ects" })?;
+    let schedule = schedule.ok_or(ParseError::Missing {
+        field: "info.schedule",
+    })?;
+    let language = language.ok_or(ParseError::Missing {
+        field: "info.language",
+    })?;
+    let duration: Result<parser::Duration> = duration.map_or_else(
         || {
             // Edge case #1: Some professors are especially bad at following structure, therefore they
             // put the duration of the course inside the "schedule" section, so will therefore try to
             // find it in there:
-            let mut e_one: Option<parser::Duration> = None;
+            let mut e_one: Option<(parser::Duration, &str, &str)> = None;
             for (key, val) in course_info {
                 match key.as_str() {
                     "Schedule" | "Skemagruppe" => {
-                        e_one = parse_duration(val).ok();
+                        e_one = parse_duration(val)
+                            .ok()
+                            .map(|d| (d, key.as_str(), val.as_str()));
                     }
                     _ => continue,
                 }
             }
-            e_one.context("Failed to get duration")
+            let (e_one, label, raw) = e_one.ok_or(ParseError::Missing {
+                field: "info.duration",
+            })?;
+            diagnostics.warn("info.duration", Heuristic::DurationFromSchedule, raw);
+            diagnostics.trace("info.duration", "panel-body", label, raw);
+            Ok(e_one)
         },
         Ok,
     );
     let duration = duration?;
-    let degree = degree.context("Failed to get degree")?;
+    let degree = degree.ok_or(ParseError::Missing {
+        field: "info.degree",
+    })?;
 
     for (key, value) in course_info {
         // Since blocks might need information on the duration, we parse block afterwards
         match key.as_str() {
-            "Placement" | "Placering" => block = Some(parse_block(value, &duration)?),
+            "Placement" | "Placering" => block = Some(parse_block(value, &duration, diagnostics)?),
             _ => continue,
         }
     }
-    let block = block.context("Failed to get block")?;
+    let block = block.ok_or(ParseError::Missing {
+        field: "info.block",
+    })?;
 
     Ok(parser::CourseInformation {
         id,
@@ -119,6 +130,21 @@ fn coerce_course_info(
     })
 }
 
+// The output field that a dt label in the course info is parsed into
+fn info_field(label: &str) -> Option<&'static str> {
+    match label {
+        "Course code" | "Kursuskode" => Some("info.id"),
+        "Language" | "Sprog" => Some("info.language"),
+        "Point" | "Credit" => Some("info.ects"),
+        "Level" | "Niveau" => Some("info.degree"),
+        "Duration" | "Varighed" => Some("info.duration"),
+        "Schedule" | "Skemagruppe" => Some("info.schedule"),
+        "Course capacity" | "Kursuskapacitet" => Some("info.capacity"),
+        "Placement" | "Placering" => Some("info.block"),
+        _ => None,
+    }
+}
+
 // Luca's research (2023-08-28)
 // Name                    | Count                     | Unique IDS
 // faculty of humanities: 173                          | HIOK, HTOB, HØEB, HØEK, HTYK, HÆGB, HEGR, HPOK, HSPK, HFMB, HFMK, HJAB, HKAK, HNAB, HNAK, HMØK, HRVB, HKUK, HLVK, HMVK, HENÅ, HASB, HHIB, HJAÆ, HÆGK, HTEK, HENB, HFAK, HCCK, HSAX, HKIÆ, HDCB, HIAB, HFIK, HFIB, HFRK, HKOB, HAIK, HENK, HANK, HIAÆ, HMØB, HHIK, HKIB, HMGK, HMKK, HOLD
@@ -136,10 +162,10 @@ fn coerce_course_info(
 // From above it is clear that the faculty of science always can be matched on with the following regex: (N|L).*
 // If the faculty is not from SCIENCE we want to return an error
 // NORS is a special case, because its a humanities course (norwegian)
-fn parse_code(code: &str) -> Result<String> {
+fn parse_code(code: &str) -> Result<CourseCode> {
     match code {
         "NORS" => bail!("Wrong faculty <EXPECTED>"),
-        code if code.starts_with('N') || code.starts_with('L') => Ok(code.to_string()),
+        code if code.starts_with('N') || code.starts_with('L') => code.parse(),
         _ => bail!("Wrong faculty <EXPECTED>"),
     }
 }
@@ -154,7 +180,7 @@ fn parse_duration(duration: &str) -> Result<parser::Duration> {
         x if duration.contains("blo") => match x {
             _ if x.contains('1') => Ok(parser::Duration::One),
             _ if x.contains('2') => Ok(parser::Duration::Two),
-            _ => bail!(format!("Unknown duration: {}", duration)),
+            _ => Err(ParseError::unknown("info.duration", duration).into()),
         },
         _ if duration.contains("sem") => Ok(parser::Duration::Two),
         _ if duration.contains("week") | duration.contains("uge")
@@ -162,11 +188,15 @@ fn parse_duration(duration: &str) -> Result<parser::Duration> {
         {
             Ok(parser::Duration::Custom(duration.to_string()))
         }
-        _ => bail!(format!("Unknown duration: {}", duration)),
+        _ => Err(ParseError::unknown("info.duration", duration).into()),
     }
 }
 
-fn parse_block(input: &str, duration: &parser::Duration) -> Result<Vec<parser::Block>> {
+fn parse_block(
+    input: &str,
+    duration: &parser::Duration,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<parser::Block>> {
     let mut blocks: Vec<parser::Block> = Vec::new();
 
     match duration {
@@ -182,6 +212,7 @@ fn parse_block(input: &str, duration: &parser::Duration) -> Result<Vec<parser::B
             }
             if blocks.is_empty() && (input.contains("Summer") || input.contains("Sommer")) {
                 blocks.push(parser::Block::Summer);
+                diagnostics.warn("info.block", Heuristic::BlockFromSeason, input);
             }
         }
         parser::Duration::Two => {
@@ -204,15 +235,18 @@ fn parse_block(input: &str, duration: &parser::Duration) -> Result<Vec<parser::B
                     blocks.push(parser::Block::Three);
                     blocks.push(parser::Block::Four);
                 }
+                if !blocks.is_empty() {
+                    diagnostics.warn("info.block", Heuristic::BlockFromSeason, input);
+                }
             }
         }
     }
 
-    ensure!(!blocks.is_empty(), "Unknown block");
+    ensure!(!blocks.is_empty(), ParseError::unknown("info.block", input));
     Ok(blocks)
 }
 
-fn parse_schedule(schedule: &str) -> Result<Vec<parser::Schedule>> {
+fn parse_schedule(schedule: &str, diagnostics: &mut Diagnostics) -> Result<Vec<parser::Schedule>> {
     // println!("Schedule info passed in: {schedule}");
     let mut schedule_vec: Vec<parser::Schedule> = Vec::new();
 
@@ -250,6 +284,9 @@ fn parse_schedule(schedule: &str) -> Result<Vec<parser::Schedule>> {
         if schedule.to_lowercase().contains("fri") {
             schedule_vec.push(parser::Schedule::B);
         }
+        if !schedule_vec.is_empty() {
+            diagnostics.warn("info.schedule", Heuristic::ScheduleFromWeekdays, schedule);
+        }
 
         if schedule.contains("Praktik")
             || schedule.contains("No scheme")
@@ -268,7 +305,7 @@ fn parse_schedule(schedule: &str) -> Result<Vec<parser::Schedule>> {
         }
 
         if schedule_vec.is_empty() {
-            bail!(format!("Unknown schedule: {}", schedule));
+            Err(ParseError::unknown("info.schedule", schedule).into())
         } else {
             Ok(schedule_vec)
         }
@@ -307,11 +344,14 @@ fn parse_degree(degree: &str) -> Result<Vec<parser::Degree>> {
         _ => (),
     }
 
-    ensure!(!result.is_empty(), "Unable to parse degree information");
+    ensure!(
+        !result.is_empty(),
+        ParseError::unknown("info.degree", degree)
+    );
     Ok(result)
 }
 
-fn parse_ects(ects: &str, dom: &VDom) -> Result<f32> {
+fn parse_ects(ects: &str, page: &Sections, diagnostics: &mut Diagnostics) -> Result<f32> {
     // println!("Ects info: {ects}"); // Fixed formatting string
 
     // Extract numeric characters, '.' and ',' from the input string
@@ -329,13 +369,13 @@ fn parse_ects(ects: &str, dom: &VDom) -> Result<f32> {
         // is instead saying something like "see description". Therefore we perform a full
         // text search through the DOM as a last resort to see wether we can parse it.
 
-        let binding = dom.outer_html();
+        let binding = page.html;
         let occurences: Vec<_> = binding.match_indices("ECTS").collect();
 
         // Extract the ECTS values from the occurences
         let mut ects_values: Vec<f32> = Vec::new();
         for x in &occurences {
-            if let Some(window) = binding.get(x.0 - 4..x.0) {
+            if let Some(window) = binding.get(x.0.saturating_sub(4)..x.0) {
                 let instance: String = window
                     .chars()
                     .filter(|x| x.is_numeric() || *x == ',' || *x == '.')
@@ -347,12 +387,14 @@ fn parse_ects(ects: &str, dom: &VDom) -> Result<f32> {
             }
         }
 
+        diagnostics.warn("info.ects", Heuristic::EctsFromFullText, ects);
+
         // After collecting the ects values, we sum them together for the final value
         let sum = ects_values.iter().sum();
         sum
     });
 
-    ensure!(ects_value > 0.0, "Unable to parse ECTS value!");
+    ensure!(ects_value > 0.0, ParseError::unknown("info.ects", ects));
     Ok(ects_value)
 }
 
@@ -369,7 +411,10 @@ fn parse_language(language: &str) -> Result<Vec<parser::Language>> {
         languages.push(parser::Language::English);
     }
 
-    ensure!(!languages.is_empty(), "Unable to parse languages");
+    ensure!(
+        !languages.is_empty(),
+        ParseError::unknown("info.language", language)
+    );
     Ok(languages)
 }
 
@@ -0,0 +1,50 @@
+// The typed errors of the parser, they are the root cause of the anyhow errors returned by `parse_course`
+// and can be recovered with `ParseError::of` to group failures by what went wrong
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// A value that the parser does not know (yet), e.g. a new department
+    UnknownValue { field: &'static str, value: String },
+    /// A field or section that is missing from the page
+    Missing { field: &'static str },
+}
+
+impl ParseError {
+    pub fn unknown(field: &'static str, value: &str) -> Self {
+        ParseError::UnknownValue {
+            field,
+            value: value.trim().to_string(),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ParseError::UnknownValue { .. } => "unknown_value",
+            ParseError::Missing { .. } => "missing",
+        }
+    }
+
+    /// The output field the error is about, in the same notation as the provenance
+    pub fn field(&self) -> &'static str {
+        match self {
+            ParseError::UnknownValue { field, .. } | ParseError::Missing { field } => field,
+        }
+    }
+
+    /// The typed error behind an anyhow error, no matter how much context was added to it
+    pub fn of(error: &anyhow::Error) -> Option<&ParseError> {
+        error.downcast_ref()
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnknownValue { field, value } => write!(f, "Unknown {field}: {value}"),
+            ParseError::Missing { field } => write!(f, "Missing {field}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -1,17 +1,19 @@
-use crate::parser::Exam;
-use anyhow::{bail, ensure, Context, Result};
+use crate::parser::{Diagnostics, Exam, ParseError, Sections};
+use anyhow::{ensure, Context, Result};
+use std::collections::BTreeMap;
 
-use tl::{NodeHandle, VDom};
+use tl::NodeHandle;
 
-pub fn parse_course_exams(dom: &VDom) -> Result<Vec<Exam>> {
-    let parser = dom.parser();
-    let exam_table = dom
-        .get_element_by_id("course-exams1")
-        .context("Unable to find exam table, this should never happen??? i think?")?
-        .get(parser)
-        .unwrap()
-        .as_tag()
-        .unwrap();
+// Every row of the exam table besides the type of assessment ends up in `raw_fields`
+pub fn parse_course_exams(
+    page: &Sections,
+    diagnostics: &mut Diagnostics,
+    raw_fields: &mut BTreeMap<String, String>,
+) -> Result<Vec<Exam>> {
+    let parser = page.parser;
+    let exam_table = page
+        .by_id("course-exams1")
+        .ok_or(ParseError::Missing { field: "exams" })?;
 
     let dts = exam_table
         .query_selector(parser, "dt")
@@ -27,29 +29,44 @@ pub fn parse_course_exams(dom: &VDom) -> Result<Vec<Exam>> {
 
     let mut exams = Vec::<Exam>::new();
     for (dt, dd) in dts.zip(dds) {
-        let dt_text = dt.get(parser).unwrap().inner_text(parser).to_string();
+        let dt_text = dt
+            .get(parser)
+            .context("Failed to get dt")?
+            .inner_text(parser)
+            .to_string();
+        let dd_text = dd
+            .get(parser)
+            .context("Failed to get dd")?
+            .inner_text(parser);
         match dt_text.as_str() {
             "Type of assessment" | "Prøveform" => {
                 let exam_boundary = dd
                     .get(parser)
-                    .unwrap()
-                    .children()
-                    .unwrap()
-                    .boundaries(parser)
-                    .unwrap();
+                    .and_then(|node| node.children()?.boundaries(parser))
+                    .ok_or(ParseError::Missing { field: "exams" })?;
                 for j in exam_boundary.0..exam_boundary.1 {
-                    let text = NodeHandle::new(j).get(parser).unwrap().inner_text(parser);
+                    let Some(node) = NodeHandle::new(j).get(parser) else {
+                        continue;
+                    };
+                    let text = node.inner_text(parser);
+                    diagnostics.trace(
+                        &format!("exams[{}]", exams.len()),
+                        "course-exams1",
+                        &dt_text,
+                        &text,
+                    );
                     exams.push(parse_text_to_exam(&text)?);
                 }
                 ensure!(
                     !exams.is_empty(),
-                    format!(
-                        "No exams found in exam table: {}",
-                        dd.get(parser).unwrap().inner_text(parser)
-                    )
+                    format!("No exams found in exam table: {dd_text}")
                 );
             }
-            _ => continue,
+            _ => {
+                raw_fields
+                    .entry(dt_text.trim().to_string())
+                    .or_insert_with(|| dd_text.trim().to_string());
+            }
         }
     }
     if exams.len() > 1 && exams[0] == exams[1] {
@@ -114,6 +131,6 @@ fn parse_text_to_exam(text: &str) -> Result<Exam> {
         {
             Ok(Exam::ContinuousAssessment)
         }
-        _ => bail!("Not implemented for exam type: {}", split[0]),
+        _ => Err(ParseError::unknown("exams", split[0]).into()),
     }
 }
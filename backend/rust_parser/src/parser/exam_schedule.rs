@@ -0,0 +1,136 @@
+// The exam schedules of the faculty say which written exams are held digitally on-site at ITX,
+// the course pages only say that the exam is written
+use crate::parser::{Course, CourseCode, Exam};
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// The codes with an ITX exam in an exam schedule as they are written, see `mark_itx` for how
+/// they are matched to the courses. The schedule is either the TSV that tabula extracts from
+/// the schedule PDF, or a text dump of the PDF (e.g. `pdftotext -layout`), in both the course
+/// code is the first code on the line of the exam
+pub fn itx_courses(schedule: &str) -> BTreeSet<CourseCode> {
+    schedule
+        .lines()
+        .filter(|line| line.contains("ITX"))
+        .filter_map(|line| {
+            line.split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+                .map(|token| token.trim_matches(|c: char| !c.is_alphanumeric()))
+                .find_map(|token| token.parse().ok())
+        })
+        .collect()
+}
+
+/// The ITX courses of a schedule file, or of every `.tsv` and `.txt` schedule in a directory
+pub fn load_itx_courses(path: &Path) -> Result<BTreeSet<CourseCode>> {
+    let mut files = vec![];
+    if path.is_dir() {
+        for entry in
+            std::fs::read_dir(path).with_context(|| format!("Could not read {}", path.display()))?
+        {
+            let file = entry?.path();
+            let hidden = file
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            let schedule = file
+                .extension()
+                .is_some_and(|ext| ext == "tsv" || ext == "txt");
+            if schedule && !hidden {
+                files.push(file);
+            }
+        }
+    } else {
+        files.push(path.to_path_buf());
+    }
+
+    let mut courses = BTreeSet::new();
+    for file in files {
+        let schedule = std::fs::read_to_string(&file)
+            .with_context(|| format!("Could not read {}", file.display()))?;
+        courses.extend(itx_courses(&schedule));
+    }
+    Ok(courses)
+}
+
+/// Turns the written exams of the course into ITX exams if the course is in `itx_courses`.
+/// The schedules use either the code of the course or of its exam, which ends in E instead of U,
+/// so a course ending in U also matches its exam code. Courses that end in E themselves only
+/// match their own code
+pub fn mark_itx(course: &mut Course, itx_courses: &BTreeSet<CourseCode>) {
+    let id = course.info.id.as_str();
+    let exam_code = id
+        .strip_suffix('U')
+        .and_then(|code| format!("{code}E").parse::<CourseCode>().ok());
+    let listed = itx_courses.contains(&course.info.id)
+        || exam_code.is_some_and(|code| itx_courses.contains(&code));
+    if !listed {
+        return;
+    }
+    for exam in &mut course.exams {
+        if let Exam::Written(minutes) = exam {
+            *exam = Exam::Itx(*minutes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(codes: &[&str]) -> BTreeSet<CourseCode> {
+        codes.iter().map(|code| code.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_itx_courses_from_tsv() {
+        let tsv = std::fs::read_to_string("./test_data/exam_schedules/winter-2023.tsv").unwrap();
+        assert_eq!(itx_courses(&tsv), codes(&["NDAA09023U", "NDAB15009E"]));
+    }
+
+    #[test]
+    fn test_itx_courses_from_text() {
+        let text = std::fs::read_to_string("./test_data/exam_schedules/summer-2024.txt").unwrap();
+        assert_eq!(itx_courses(&text), codes(&["LSLS10061E", "NMAA04032U"]));
+    }
+
+    #[test]
+    fn test_load_itx_courses() {
+        assert_eq!(
+            load_itx_courses(Path::new("./test_data/exam_schedules")).unwrap(),
+            codes(&["LSLS10061E", "NDAA09023U", "NDAB15009E", "NMAA04032U"])
+        );
+    }
+
+    #[test]
+    fn test_mark_itx() {
+        let mut course = crate::parser::test_course("LSLS10061U");
+        course.exams = vec![Exam::Written(Some(240)), Exam::Oral(Some(30))];
+
+        mark_itx(&mut course, &codes(&["NDAB15009U"]));
+        assert_eq!(course.exams[0], Exam::Written(Some(240)));
+        // the schedule has the code of the exam
+        mark_itx(&mut course, &codes(&["LSLS10061E"]));
+        assert_eq!(
+            course.exams,
+            vec![Exam::Itx(Some(240)), Exam::Oral(Some(30))]
+        );
+        assert_eq!(
+            serde_json::to_string(&course.exams[0]).unwrap(),
+            r#"{"ITX":240}"#
+        );
+    }
+
+    #[test]
+    fn test_mark_itx_e_course() {
+        let mut course = crate::parser::test_course("LSLS10061U");
+        course.info.id = "HØEB01001E".parse().unwrap();
+        course.exams = vec![Exam::Written(Some(240))];
+        let schedule = "HØEB01001E\tØkonomi\tSkriftlig prøve\t2024-01-22\tITX\n";
+
+        // a course ending in E is not the exam code of a course ending in U
+        mark_itx(&mut course, &codes(&["HØEB01001U"]));
+        assert_eq!(course.exams, vec![Exam::Written(Some(240))]);
+        mark_itx(&mut course, &itx_courses(schedule));
+        assert_eq!(course.exams, vec![Exam::Itx(Some(240))]);
+    }
+}
@@ -1,79 +1,240 @@
 use crate::parser;
-use crate::parser::LogisticInformation;
-use anyhow::{bail, ensure, Result};
-use tl::{NodeHandle, VDom};
-
-// Convert two chars in a string to a u8
-fn double_hex_to_u8(hex: &str) -> u8 {
-    let mut chars = hex.chars();
-    let first = chars.next().unwrap();
-    let second = chars.next().unwrap();
-    let first = first.to_digit(16).unwrap() as u8;
-    let second = second.to_digit(16).unwrap() as u8;
-    (first << 4) | second
+use crate::parser::sections::{self, BlockItem, HeadedBlock, Sections};
+use crate::parser::{Diagnostics, LogisticInformation, OrganisationMapping, ParseError};
+use anyhow::{ensure, Result};
+use std::sync::LazyLock;
+use tl::Node;
+
+// The deobfuscated email is right when it looks like an email
+static EMAIL: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(.+@.+\..+)").expect("the email regex is valid"));
+
+// Convert two hex digits to a u8, None if they are not hex digits
+fn double_hex_to_u8(hex: &[u8]) -> Option<u8> {
+    let first = (*hex.first()? as char).to_digit(16)? as u8;
+    let second = (*hex.get(1)? as char).to_digit(16)? as u8;
+    Some((first << 4) | second)
 }
 
+// The pages obfuscate the emails as `<n>-<hex>`, the bytes of the email shifted up by an offset,
+// where n is the position of the '@' so the offset is how far the byte at n is from '@'
 fn deobfuscate_email(obfuscated_email: &str) -> Result<String> {
-    let mut split = obfuscated_email.split('-');
-    if split.clone().count() == 1 {
+    let Some((at, hex)) = obfuscated_email
+        .split_once('-')
+        .filter(|(at, _)| !at.is_empty() && at.bytes().all(|b| b.is_ascii_digit()))
+    else {
+        // a plain email, which can have a '-' of its own
         return Ok(obfuscated_email.to_string());
+    };
+    let unknown = || ParseError::unknown("logistics.coordinators", obfuscated_email);
+    // anything that is not pairs of hex digits was not obfuscated by the page
+    ensure!(
+        hex.len().is_multiple_of(2) && hex.bytes().all(|b| b.is_ascii_hexdigit()),
+        unknown()
+    );
+    let bytes: Vec<u8> = hex
+        .as_bytes()
+        .chunks(2)
+        .filter_map(double_hex_to_u8)
+        .collect();
+    let offset = at
+        .parse::<usize>()
+        .ok()
+        .and_then(|at| bytes.get(at))
+        .and_then(|byte| byte.checked_sub(b'@'))
+        .ok_or_else(unknown)?;
+    let email: String = bytes
+        .iter()
+        .map(|byte| {
+            let byte = byte.checked_sub(offset)?;
+            byte.is_ascii_graphic().then_some(char::from(byte))
+        })
+        .collect::<Option<_>>()
+        .ok_or_else(unknown)?;
+    ensure!(EMAIL.is_match(&email), unknown());
+    Ok(email)
+}
+
+/// Lowercases an email and removes the `mailto:` and query of a link to it
+pub(crate) fn normalise_email(email: &str) -> String {
+    let email = email.trim();
+    let email = match email.get(..7) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => &email[7..],
+        _ => email,
+    };
+    let email = email.split('?').next().unwrap_or_default();
+    email.trim().to_lowercase()
+}
+
+// The words that belong to the family name after them, e.g. Ludwig van Beethoven
+const NAME_PARTICLES: [&str; 9] = ["van", "von", "de", "der", "den", "du", "la", "le", "af"];
+
+/// Splits a name into given and family names. The family name is the last word and the particles
+/// before it, a name written "Family, Given" is the other way round and a single word is a family name
+pub(crate) fn split_name(name: &str) -> (String, String) {
+    let join = |words: &[&str]| words.join(" ");
+    if let Some((family, given)) = name.split_once(',') {
+        let given: Vec<&str> = given.split_whitespace().collect();
+        let family: Vec<&str> = family.split_whitespace().collect();
+        return (join(&given), join(&family));
     }
-    let text = split.next_back().unwrap();
-
-    let mut email = String::new();
-    // Iterate through the split and move in steps of two
-    // we offset the numbers by 0..25 since thats how they are obfuscated
-    // if the regex matches an email we return it
-    // else we continue incrementing the offset and hoping we find a match
-    let regex = regex::Regex::new(r"(.+@.+\..+)").unwrap();
-    for i in 0..25 {
-        for j in (0..text.len()).step_by(2) {
-            let hex = &text[j..j + 2];
-            let u8 = double_hex_to_u8(hex) - i;
-            email.push(u8 as char);
+    let words: Vec<&str> = name.split_whitespace().collect();
+    let Some(mut family_start) = words.len().checked_sub(1) else {
+        return (String::new(), String::new());
+    };
+    // the first word is always a given name
+    while family_start > 1 && NAME_PARTICLES.contains(&words[family_start - 1]) {
+        family_start -= 1;
+    }
+    (join(&words[..family_start]), join(&words[family_start..]))
+}
+
+// The envelope of a coordinator opens the obfuscated email with `mail('<n>-<hex>')`, other pages
+// link to the email with `mailto:` and the printed page has the obfuscated email in parenthesis
+fn parse_coordinator(parser: &tl::Parser, item: &BlockItem) -> Result<parser::Coordinator> {
+    let unknown = || ParseError::unknown("logistics.coordinators", &item.text);
+    let mut obfuscated = None;
+    let mut link = None;
+    for tag in item
+        .tag
+        .children()
+        .all(parser)
+        .iter()
+        .filter_map(Node::as_tag)
+    {
+        let attributes = tag.attributes();
+        if let Some(onclick) = attributes.get("onclick").flatten() {
+            let onclick = onclick.as_utf8_str();
+            if let Some((argument, _)) = onclick
+                .trim()
+                .strip_prefix("mail('")
+                .and_then(|rest| rest.split_once('\''))
+            {
+                obfuscated.get_or_insert_with(|| argument.to_string());
+            }
         }
+        if let Some(href) = attributes.get("href").flatten() {
+            let href = href.as_utf8_str();
+            if href.trim().to_lowercase().starts_with("mailto:") {
+                link.get_or_insert_with(|| (href.to_string(), tag.inner_text(parser).to_string()));
+            }
+        }
+    }
+    // the email should be removed from the name, it is in the elements or enclosed in parenthesis
+    let own_text: String = item
+        .tag
+        .children()
+        .top()
+        .iter()
+        .filter_map(|child| child.get(parser)?.as_raw())
+        .map(|raw| raw.as_utf8_str())
+        .collect();
+    let before_parenthesis = |text: &str| {
+        text.split('(')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string()
+    };
+    let name = [
+        before_parenthesis(&own_text),
+        link.as_ref()
+            .map(|(_, text)| text.trim().to_string())
+            .unwrap_or_default(),
+        before_parenthesis(&item.text),
+    ]
+    .into_iter()
+    .find(|name| !name.is_empty() && !name.contains('@'))
+    .ok_or_else(unknown)?;
 
-        if regex.is_match(&email) {
-            return Ok(email);
+    let email = if let Some(obfuscated) = obfuscated {
+        deobfuscate_email(&obfuscated)?
+    } else if let Some((href, _)) = link {
+        href
+    } else {
+        let (_, rest) = item.text.split_once('(').ok_or_else(unknown)?;
+        deobfuscate_email(rest.split(')').next().unwrap_or_default().trim())?
+    };
+    Ok(parser::Coordinator::new(name, email))
+}
+
+fn is_faculty_heading(h5: &str) -> bool {
+    matches!(h5, "Udbydende fakultet" | "Contracting faculty")
+}
+
+fn is_department_heading(h5: &str) -> bool {
+    h5.contains("institut") || h5.contains("department")
+}
+
+/// The department and faculty names of the page as they are written, before they are mapped
+pub fn organisation_names(page: &Sections) -> Result<(Vec<String>, Vec<String>)> {
+    let mut departments = vec![];
+    let mut faculties = vec![];
+    for (h5, lis) in extract_h5_li_pairs(page) {
+        if is_faculty_heading(&h5) {
+            faculties.extend(lis.into_iter().take(1));
+        } else if is_department_heading(&h5) {
+            departments.extend(lis);
         }
-        email.clear();
     }
-    bail!("Unable to deobfuscate email: {}", obfuscated_email)
+    Ok((departments, faculties))
 }
 
-pub fn parse_logistic_info(dom: &VDom) -> Result<LogisticInformation> {
+pub fn parse_logistic_info(
+    page: &Sections,
+    diagnostics: &mut Diagnostics,
+    organisations: &OrganisationMapping,
+) -> Result<LogisticInformation> {
     // Extract the information from the dom.
-    let info: Vec<(String, Vec<String>)> = extract_h5_li_pairs(dom)?;
+    let blocks = logistic_blocks(page);
 
     let mut departments: Vec<parser::Department> = vec![];
     let mut coordinators: Vec<parser::Coordinator> = vec![];
     let mut faculty: Option<parser::Faculty> = None;
 
-    for (h5, lis) in &info {
+    for block in &blocks {
+        let h5 = &block.heading;
+        let lis: Vec<&String> = block.items.iter().map(|item| &item.text).collect();
         match h5.as_str() {
             "Kursusansvarlige" | "Course Coordinators" => {
-                for li in lis {
-                    // the email should be removed from the name, it is enclosed in parenthesis
-                    let mut split = li.split('(');
-                    let name = split.next().unwrap().trim().to_string();
-                    let obfuscated_email =
-                        split.next().unwrap().split(')').next().unwrap().to_string();
-                    let email = deobfuscate_email(&obfuscated_email)?;
-                    coordinators.push(parser::Coordinator { name, email });
+                for (i, item) in block.items.iter().enumerate() {
+                    diagnostics.trace(
+                        &format!("logistics.coordinators[{i}]"),
+                        "panel-body",
+                        h5,
+                        &item.text,
+                    );
+                    coordinators.push(parse_coordinator(page.parser, item)?);
                 }
             }
-            "Udbydende fakultet" | "Contracting faculty" => {
-                let faculty_str = lis.first().unwrap();
-                match faculty_str.as_str() {
-                    "Det Natur- og Biovidenskabelige Fakultet" | "Faculty of Science" => {
-                        faculty = Some(parser::Faculty::Science)
-                    }
-                    _ => bail!("Unknown faculty: {} <EXPECTED>", faculty_str),
+            _ if is_faculty_heading(h5) => {
+                let faculty_str = lis.first().ok_or(ParseError::Missing {
+                    field: "logistics.faculty",
+                })?;
+                diagnostics.trace("logistics.faculty", "panel-body", h5, faculty_str);
+                // the courses of the unsupported faculties of the mapping are skipped
+                let mapped = organisations.faculty(faculty_str)?;
+                if matches!(mapped, parser::Faculty::Unknown(_)) {
+                    diagnostics.warn(
+                        "logistics.faculty",
+                        parser::Heuristic::UnknownFaculty,
+                        faculty_str,
+                    );
                 }
+                faculty = Some(mapped);
             }
-            _ if h5.contains("institut") || h5.contains("department") => {
-                for li in lis {
-                    departments.push(parser::Department::from_str(li)?);
+            _ if is_department_heading(h5) => {
+                for (i, li) in lis.iter().enumerate() {
+                    diagnostics.trace(&format!("logistics.departments[{i}]"), "panel-body", h5, li);
+                    let department = organisations.department(li)?;
+                    if matches!(department, parser::Department::Unknown(_)) {
+                        diagnostics.warn(
+                            &format!("logistics.departments[{i}]"),
+                            parser::Heuristic::UnknownDepartment,
+                            li,
+                        );
+                    }
+                    departments.push(department);
                 }
             }
 
@@ -83,45 +244,156 @@ pub fn parse_logistic_info(dom: &VDom) -> Result<LogisticInformation> {
     // ensure we have
     ensure!(
         !departments.is_empty(),
-        format!("No departments found in logistic information: {:?}", info)
+        format!(
+            "No departments found in logistic information: {:?}",
+            extract_h5_li_pairs(page)
+        )
     );
 
     Ok(parser::LogisticInformation {
         departments,
-        faculty: faculty.unwrap(),
+        faculty: faculty.ok_or(ParseError::Missing {
+            field: "logistics.faculty",
+        })?,
         coordinators,
     })
 }
 
-pub fn extract_h5_li_pairs(dom: &VDom) -> Result<Vec<(String, Vec<String>)>> {
-    let parser = dom.parser();
+// The headed blocks of the panel-bodies with h5s
+fn logistic_blocks<'a>(page: &Sections<'a>) -> Vec<HeadedBlock<'a>> {
+    page.logistics
+        .iter()
+        .flat_map(|panel_body| sections::headed_blocks(page.parser, panel_body, "h5"))
+        .collect()
+}
 
-    let raw_panel_bodies = dom.get_elements_by_class_name("panel-body");
-    let panel_bodies =
-        raw_panel_bodies.map(|panel_body| panel_body.get(parser).unwrap().as_tag().unwrap());
+pub fn extract_h5_li_pairs(page: &Sections) -> Vec<(String, Vec<String>)> {
+    logistic_blocks(page)
+        .into_iter()
+        .map(|block| {
+            let texts = block.items.into_iter().map(|item| item.text).collect();
+            (block.heading, texts)
+        })
+        .collect()
+}
 
-    let mut pairs: Vec<(String, Vec<String>)> = vec![];
-    for panel_body in panel_bodies {
-        let h5s = panel_body.query_selector(parser, "h5").unwrap();
-        // if it contains h5s, we have found the right body
-        if h5s.clone().count() > 0 {
-            for h5 in h5s {
-                let h5_text = h5.get(parser).unwrap().inner_text(parser).to_string();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_course, test_page, Coordinator, EmailKind};
 
-                let inner_handle = h5.get_inner(); // This is the handle to the h5 tag
+    #[test]
+    fn test_layout_variants() {
+        let html = test_page("LSLS10061U");
+        let expected = parse_course(&html).unwrap().course.logistics;
 
-                // by magic we know that offsetting by 2 gives us the ul tag
-                let ul_handle = NodeHandle::new(inner_handle + 2).get(parser).unwrap();
+        let spaced = html
+            .replace("</h5>", "</h5>\n  <!-- list -->\n  ")
+            .replace("</ul>", "</ul>\n");
+        let wrapped = html
+            .replace("<h5", "<div class=\"section\"><h5")
+            .replace("</ul>", "</ul></div>");
+        let nested = html
+            .replace(
+                "<ul class=\"list-unstyled\">",
+                "<div><ul class=\"list-unstyled\">",
+            )
+            .replace("</ul>", "</ul></div>");
+        for variant in [spaced, wrapped, nested] {
+            assert_eq!(parse_course(&variant).unwrap().course.logistics, expected);
+        }
+    }
 
-                // get the chldren inside of next_sibling
-                let mut children = vec![];
-                for child in ul_handle.as_tag().unwrap().children().top().iter() {
-                    let child_text = child.get(parser).unwrap().inner_text(parser).to_string();
-                    children.push(child_text);
-                }
-                pairs.push((h5_text, children));
-            }
+    #[test]
+    fn test_deobfuscate_email() {
+        // the offsets of these differ, the prefix gives each of them
+        for (obfuscated, email) in [
+            (
+                "6-66686f3b383c4465707971726d326f7932686f",
+                "bdk748@alumni.ku.dk",
+            ),
+            (
+                "15-7173767869723265726869767769724477797268326f7932686f",
+                "morten.andersen@sund.ku.dk",
+            ),
+            (
+                "15-7375787a6b743467746a6b78796b7446797b746a34717b346a71",
+                "morten.andersen@sund.ku.dk",
+            ),
+            ("8-76737275756c716a43676c316e7831676e", "sporring@di.ku.dk"),
+            ("anne-marie@ku.dk", "anne-marie@ku.dk"),
+        ] {
+            assert_eq!(
+                deobfuscate_email(obfuscated).unwrap(),
+                email,
+                "{obfuscated}"
+            );
+        }
+        // the '@' is past the end, and the offset would shift the bytes below zero
+        assert!(deobfuscate_email("40-66686f3b383c").is_err());
+        assert!(deobfuscate_email("0-20").is_err());
+    }
+
+    #[test]
+    fn test_normalise_email() {
+        assert_eq!(normalise_email(" HHBruun@bio.ku.dk "), "hhbruun@bio.ku.dk");
+        assert_eq!(
+            normalise_email("MAILTO:abc@di.ku.dk?subject=Course"),
+            "abc@di.ku.dk"
+        );
+        assert_eq!(EmailKind::of("abc@di.ku.dk"), EmailKind::Staff);
+        assert_eq!(EmailKind::of("abc123@alumni.ku.dk"), EmailKind::Alumni);
+        assert_eq!(EmailKind::of("abc@dtu.dk"), EmailKind::External);
+    }
+
+    #[test]
+    fn test_split_name() {
+        for (name, given, family) in [
+            ("Kirsten Carlsen", "Kirsten", "Carlsen"),
+            ("Niels Anne Jacob Obers", "Niels Anne Jacob", "Obers"),
+            ("Ludwig van Beethoven", "Ludwig", "van Beethoven"),
+            ("Hans van der Berg", "Hans", "van der Berg"),
+            ("Carlsen, Kirsten", "Kirsten", "Carlsen"),
+            ("Madonna", "", "Madonna"),
+            ("", "", ""),
+        ] {
+            assert_eq!(
+                split_name(name),
+                (given.to_string(), family.to_string()),
+                "{name}"
+            );
+        }
+    }
+
+    fn coordinators(li: &str) -> Result<Vec<Coordinator>> {
+        let html = test_page("LSLS10061U");
+        let start = html.find("<li>Kirsten Carlsen").unwrap();
+        let end = start + html[start..].find("</li>").unwrap() + "</li>".len();
+        let page = format!("{}{li}{}", &html[..start], &html[end..]);
+        Ok(parse_course(&page)?.course.logistics.coordinators)
+    }
+
+    #[test]
+    fn test_coordinator_sources() {
+        let expected = vec![Coordinator::new(
+            "Kirsten Carlsen".to_string(),
+            "bdk748@alumni.ku.dk".to_string(),
+        )];
+        for li in [
+            // only the envelope, and only the printed email
+            "<li>Kirsten Carlsen <span onclick=\"mail('6-66686f3b383c4465707971726d326f7932686f');\"></span></li>",
+            "<li>Kirsten Carlsen (6-66686f3b383c4465707971726d326f7932686f)</li>",
+            "<li>Kirsten Carlsen (BDK748@alumni.ku.dk)</li>",
+            "<li>Kirsten Carlsen <a href=\"mailto:bdk748@alumni.ku.dk\">bdk748@alumni.ku.dk</a></li>",
+            "<li><a href=\"mailto:Bdk748@Alumni.ku.dk?subject=Kursus\">Kirsten Carlsen</a></li>",
+        ] {
+            assert_eq!(coordinators(li).unwrap(), expected, "{li}");
         }
+        let coordinator = &expected[0];
+        assert_eq!(coordinator.given_name, "Kirsten");
+        assert_eq!(coordinator.family_name, "Carlsen");
+        assert_eq!(coordinator.email_kind, Some(EmailKind::Alumni));
+
+        assert!(coordinators("<li>Kirsten Carlsen</li>").is_err());
     }
-    Ok(pairs)
 }
@@ -0,0 +1,142 @@
+// `parse_course` has to return an error instead of panicking, no matter what the page looks like.
+// The pages in test_data are mutated at random and fed to the parser, a panic fails the test
+use crate::parser::{parse_course, ParseError};
+use proptest::prelude::*;
+use proptest::sample::Index;
+
+const COORDINATOR: &str = "6-66686f3b383c4465707971726d326f7932686f";
+
+// Ids, classes and tags the parser navigates by, removing or duplicating them breaks the layout it expects
+const MARKERS: &[&str] = &[
+    "course-exams1",
+    "course-load",
+    "course-content",
+    "panel-body",
+    "panel-title",
+    "courseTitle",
+    "<h5",
+    "</h5>",
+    "<ul",
+    "<li>",
+    "<dl",
+    "<dt>",
+    "<dd>",
+    "ECTS",
+    "(",
+    ")",
+    "-",
+    ", ",
+];
+
+fn seeds() -> Vec<String> {
+    let mut pages: Vec<_> = std::fs::read_dir(crate::parser::TEST_HTMLS_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    pages.sort();
+    pages
+        .iter()
+        .map(|path| std::fs::read_to_string(path).unwrap())
+        .collect()
+}
+
+fn seed(index: &Index) -> String {
+    let seeds = seeds();
+    seeds[index.index(seeds.len())].clone()
+}
+
+// A byte offset in the page that is on a char boundary
+fn offset(page: &str, index: &Index) -> usize {
+    let mut offset = index.index(page.len() + 1);
+    while !page.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+fn with_coordinator(email: &str) -> String {
+    seeds()[0].replace(COORDINATOR, email)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn arbitrary_text(text in any::<String>()) {
+        let _ = parse_course(&text);
+    }
+
+    #[test]
+    fn truncated(seed_index: Index, end: Index) {
+        let page = seed(&seed_index);
+        let _ = parse_course(&page[..offset(&page, &end)]);
+    }
+
+    #[test]
+    fn range_replaced(seed_index: Index, a: Index, b: Index, text in ".{0,40}") {
+        let mut page = seed(&seed_index);
+        let (a, b) = (offset(&page, &a), offset(&page, &b));
+        page.replace_range(a.min(b)..a.max(b), &text);
+        let _ = parse_course(&page);
+    }
+
+    #[test]
+    fn range_duplicated(seed_index: Index, a: Index, b: Index) {
+        let mut page = seed(&seed_index);
+        let (a, b) = (offset(&page, &a), offset(&page, &b));
+        let range = page[a.min(b)..a.max(b)].to_string();
+        page.insert_str(a.max(b), &range);
+        let _ = parse_course(&page);
+    }
+
+    #[test]
+    fn marker_replaced(seed_index: Index, marker in prop::sample::select(MARKERS), text in ".{0,20}") {
+        let page = seed(&seed_index).replace(marker, &text);
+        let _ = parse_course(&page);
+    }
+
+    #[test]
+    fn coordinator_replaced(text in "[-0-9a-zæø()]{0,30}") {
+        let _ = parse_course(&with_coordinator(&text));
+    }
+}
+
+#[test]
+fn test_malformed_coordinator_email() {
+    for email in ["6-66686f3", "6-zz", "6-æø", "6-", "6-0000"] {
+        let error = parse_course(&with_coordinator(email)).unwrap_err();
+        assert_eq!(
+            ParseError::of(&error),
+            Some(&ParseError::unknown("logistics.coordinators", email)),
+            "{email}"
+        );
+    }
+}
+
+#[test]
+fn test_coordinator_without_email() {
+    // neither the envelope nor the text in parenthesis
+    let page = seeds()[0]
+        .replace(&format!(" onclick=\"mail('{COORDINATOR}');\""), "")
+        .replace("(<span class=\"unmangle\">", "<span class=\"unmangle\">");
+    let error = parse_course(&page).unwrap_err();
+    assert_eq!(
+        ParseError::of(&error).map(|e| e.kind()),
+        Some("unknown_value")
+    );
+}
+
+#[test]
+fn test_missing_faculty() {
+    let page = seeds()[0].replace(
+        "<h5 class=\"panel-title\">Udbydende fakultet</h5><ul class=\"list-unstyled\"><li>Det Natur- og Biovidenskabelige Fakultet</li></ul>",
+        "",
+    );
+    let error = parse_course(&page).unwrap_err();
+    assert_eq!(
+        ParseError::of(&error),
+        Some(&ParseError::Missing {
+            field: "logistics.faculty"
+        })
+    );
+}
@@ -0,0 +1,201 @@
+// Maps the names of departments and faculties on the course pages to the enums of the output,
+// the names are read from a mapping file so a renamed institute does not need a new parser
+use crate::parser::{logistic_information, Department, Faculty, Sections};
+use anyhow::{bail, Context, Result};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// The mapping that is used unless another mapping file is given
+pub const DEFAULT_MAPPING: &str = include_str!("../../organisations.json");
+
+// The mapping file lists the names of every variant, the unsupported departments and faculties
+// are skipped like cancelled courses with the reason as the error
+#[derive(Debug, Deserialize)]
+struct MappingFile {
+    departments: BTreeMap<String, Vec<String>>,
+    faculties: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    unsupported_departments: BTreeMap<String, String>,
+    #[serde(default)]
+    unsupported_faculties: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrganisationMapping {
+    departments: BTreeMap<String, Department>,
+    faculties: BTreeMap<String, Faculty>,
+    unsupported_departments: BTreeMap<String, String>,
+    unsupported_faculties: BTreeMap<String, String>,
+}
+
+/// A department or faculty name of a page that is not in the mapping
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Unmapped {
+    Department(String),
+    Faculty(String),
+}
+
+// The pages break long names over several lines
+fn normalise(name: &str) -> String {
+    name.replace('\n', " ").trim().to_string()
+}
+
+fn normalise_keys(names: BTreeMap<String, String>) -> BTreeMap<String, String> {
+    names
+        .into_iter()
+        .map(|(name, reason)| (normalise(&name), reason))
+        .collect()
+}
+
+// Every name of every variant, keyed by the normalised name
+fn aliases<T: DeserializeOwned + Clone>(
+    variants: BTreeMap<String, Vec<String>>,
+) -> Result<BTreeMap<String, T>> {
+    let mut aliases = BTreeMap::new();
+    for (variant, names) in variants {
+        let value: T = serde_json::from_value(serde_json::Value::String(variant.clone()))
+            .with_context(|| format!("Unknown variant in organisation mapping: {variant}"))?;
+        for name in names {
+            if aliases.insert(normalise(&name), value.clone()).is_some() {
+                bail!("{name} is mapped more than once in the organisation mapping");
+            }
+        }
+    }
+    Ok(aliases)
+}
+
+impl OrganisationMapping {
+    pub fn from_json(json: &str) -> Result<Self> {
+        let file: MappingFile =
+            serde_json::from_str(json).context("Invalid organisation mapping")?;
+        Ok(Self {
+            departments: aliases(file.departments)?,
+            faculties: aliases(file.faculties)?,
+            unsupported_departments: normalise_keys(file.unsupported_departments),
+            unsupported_faculties: normalise_keys(file.unsupported_faculties),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        Self::from_json(&json).with_context(|| format!("Could not load {}", path.display()))
+    }
+
+    /// The mapping of `organisations.json` that is compiled into the parser
+    pub fn embedded() -> &'static Self {
+        static EMBEDDED: OnceLock<OrganisationMapping> = OnceLock::new();
+        EMBEDDED.get_or_init(|| {
+            Self::from_json(DEFAULT_MAPPING).expect("the embedded organisation mapping is valid")
+        })
+    }
+
+    /// The department of a name, a name that is not in the mapping is kept as an unknown department
+    pub fn department(&self, name: &str) -> Result<Department> {
+        let name = normalise(name);
+        if let Some(reason) = self.unsupported_departments.get(&name) {
+            bail!("{reason} <EXPECTED>");
+        }
+        Ok(self
+            .departments
+            .get(&name)
+            .cloned()
+            .unwrap_or(Department::Unknown(name)))
+    }
+
+    /// The faculty of a name, a name that is not in the mapping is kept as an unknown faculty
+    pub fn faculty(&self, name: &str) -> Result<Faculty> {
+        let name = normalise(name);
+        if let Some(reason) = self.unsupported_faculties.get(&name) {
+            bail!("{reason} <EXPECTED>");
+        }
+        Ok(self
+            .faculties
+            .get(&name)
+            .cloned()
+            .unwrap_or(Faculty::Unknown(name)))
+    }
+
+    /// The department and faculty names of a page that this mapping does not know,
+    /// the unsupported departments and faculties are known
+    pub fn unmapped(&self, html: &str) -> Result<Vec<Unmapped>> {
+        let dom = tl::parse(html, tl::ParserOptions::default())?;
+        let (departments, faculties) =
+            logistic_information::organisation_names(&Sections::new(&dom, html))?;
+        let departments = departments
+            .iter()
+            .map(|name| normalise(name))
+            .filter(|name| {
+                !self.departments.contains_key(name)
+                    && !self.unsupported_departments.contains_key(name)
+            })
+            .map(Unmapped::Department);
+        let faculties = faculties
+            .iter()
+            .map(|name| normalise(name))
+            .filter(|name| {
+                !self.faculties.contains_key(name) && !self.unsupported_faculties.contains_key(name)
+            })
+            .map(Unmapped::Faculty);
+        Ok(departments.chain(faculties).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_mapping() {
+        let mapping = OrganisationMapping::embedded();
+        assert_eq!(
+            mapping.department("Datalogisk\nInstitut").unwrap(),
+            Department::ComputerScience
+        );
+        assert_eq!(
+            mapping.department("Department of Nowhere").unwrap(),
+            Department::Unknown("Department of Nowhere".into())
+        );
+        let nordic = mapping
+            .department("Institut for Nordiske Studier og Sprogvidenskab")
+            .unwrap_err();
+        assert!(nordic.to_string().contains("<EXPECTED>"));
+        assert_eq!(
+            mapping.faculty("Faculty of Science").unwrap(),
+            Faculty::Science
+        );
+        assert_eq!(
+            mapping.faculty("Faculty of Astrology").unwrap(),
+            Faculty::Unknown("Faculty of Astrology".into())
+        );
+        let humanities = mapping.faculty("Faculty of Humanities").unwrap_err();
+        assert!(humanities.to_string().contains("<EXPECTED>"));
+    }
+
+    #[test]
+    fn test_invalid_mapping() {
+        let unknown_variant =
+            r#"{"departments": {"Astrology": ["Institut for Astrologi"]}, "faculties": {}}"#;
+        assert!(OrganisationMapping::from_json(unknown_variant).is_err());
+        let twice = r#"{"departments": {"Biology": ["Biologisk Institut"], "Chemistry": ["Biologisk Institut"]}, "faculties": {}}"#;
+        assert!(OrganisationMapping::from_json(twice).is_err());
+    }
+
+    #[test]
+    fn test_unmapped() {
+        let html = crate::parser::test_page("LSLS10061U");
+        assert_eq!(OrganisationMapping::embedded().unmapped(&html).unwrap(), []);
+
+        // a mapping from before the institute was named
+        let mapping = OrganisationMapping::from_json(
+            r#"{"departments": {}, "faculties": {"Science": ["Faculty of Science"]}}"#,
+        )
+        .unwrap();
+        let unmapped = mapping.unmapped(&html).unwrap();
+        assert_eq!(unmapped.len(), 2);
+        assert!(matches!(&unmapped[0], Unmapped::Department(_)));
+        assert!(matches!(&unmapped[1], Unmapped::Faculty(name) if name.contains("Natur")));
+    }
+}
@@ -0,0 +1,265 @@
+// The versioned shape of the json that the parser writes for every course
+use crate::parser::{Course, Provenance};
+use anyhow::{bail, ensure, Context, Result};
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec};
+use schemars::{schema_for, JsonSchema, Map};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Bumped whenever the json written for a course changes in a way that readers have to know about,
+/// e.g. a field being renamed, removed or changing type. Adding a field does not need a new version.
+/// Version 2 allows `{"Unknown": <name>}` for the departments and the faculty
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// The oldest schema version the current types can still read, every change since then
+/// only allowed more values
+pub const OLDEST_READABLE_VERSION: u32 = 1;
+
+/// A course json file as it is read back from the json directory,
+/// `CourseOutput` is the borrowed version that the parser writes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(title = "Course")]
+pub struct CourseRecord {
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub course: Course,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Vec<Provenance>>,
+}
+
+/// The JSON Schema of a course json file, it is generated from the same types that are
+/// (de)serialised, so it can not drift from what the parser writes.
+/// The objects of the structs have no other keys than their fields
+pub fn course_schema() -> RootSchema {
+    let mut schema = schema_for!(CourseRecord);
+    close_object(&mut schema.schema);
+    for definition in schema.definitions.values_mut() {
+        if let Schema::Object(definition) = definition {
+            close_object(definition);
+        }
+    }
+    schema
+}
+
+// The structs are objects with properties, the maps and the enum variants already say
+// what other keys they allow
+fn close_object(schema: &mut SchemaObject) {
+    if let Some(object) = &mut schema.object {
+        if !object.properties.is_empty() && object.additional_properties.is_none() {
+            object.additional_properties = Some(Box::new(Schema::Bool(false)));
+        }
+    }
+}
+
+/// Checks that a course json file was written with the current schema,
+/// files without a `schema_version` were written before it was introduced
+pub fn validate_course(json: &str) -> Result<CourseRecord> {
+    read_versions(json, SCHEMA_VERSION)
+}
+
+/// Reads a course json file written with the current schema or an older one that the current
+/// types can still read, e.g. to compare the catalogues from before and after a parser change
+pub fn read_course(json: &str) -> Result<CourseRecord> {
+    read_versions(json, OLDEST_READABLE_VERSION)
+}
+
+fn read_versions(json: &str, oldest: u32) -> Result<CourseRecord> {
+    let value: serde_json::Value = serde_json::from_str(json).context("Not valid json")?;
+    let version = value
+        .get("schema_version")
+        .context("Missing schema_version, the file predates the versioned schema")?;
+    ensure!(
+        version
+            .as_u64()
+            .is_some_and(|version| (oldest.into()..=SCHEMA_VERSION.into()).contains(&version)),
+        "Schema version is {version}, expected {SCHEMA_VERSION}, parse the pages again to update the file"
+    );
+    // serde ignores the keys it does not know, so they are checked against the schema first
+    let schema = course_schema();
+    let mut unknown = vec![];
+    unknown_keys(
+        &value,
+        &schema.schema,
+        &schema.definitions,
+        "",
+        &mut unknown,
+    );
+    if !unknown.is_empty() {
+        bail!(
+            "Keys that are not in the course schema: {}",
+            unknown.join(", ")
+        );
+    }
+    serde_json::from_value(value).context("Does not match the course schema")
+}
+
+// Whether the value is of a type the schema allows, a schema without a type allows any
+fn fits(value: &Value, schema: &SchemaObject) -> bool {
+    let Some(types) = &schema.instance_type else {
+        return true;
+    };
+    let allows = |instance_type| match types {
+        SingleOrVec::Single(single) => **single == instance_type,
+        SingleOrVec::Vec(types) => types.contains(&instance_type),
+    };
+    match value {
+        Value::Null => allows(InstanceType::Null),
+        Value::Bool(_) => allows(InstanceType::Boolean),
+        Value::Number(n) => {
+            allows(InstanceType::Number)
+                || ((n.is_u64() || n.is_i64()) && allows(InstanceType::Integer))
+        }
+        Value::String(_) => allows(InstanceType::String),
+        Value::Array(_) => allows(InstanceType::Array),
+        Value::Object(_) => allows(InstanceType::Object),
+    }
+}
+
+// Collects the paths of the keys of `value` that `schema` does not allow
+fn unknown_keys(
+    value: &Value,
+    schema: &SchemaObject,
+    definitions: &Map<String, Schema>,
+    path: &str,
+    unknown: &mut Vec<String>,
+) {
+    if let Some(reference) = &schema.reference {
+        let definition = reference
+            .strip_prefix("#/definitions/")
+            .and_then(|name| definitions.get(name));
+        if let Some(Schema::Object(definition)) = definition {
+            unknown_keys(value, definition, definitions, path, unknown);
+        }
+        return;
+    }
+    if let Some(subschemas) = &schema.subschemas {
+        for subschema in subschemas.all_of.iter().flatten() {
+            if let Schema::Object(subschema) = subschema {
+                unknown_keys(value, subschema, definitions, path, unknown);
+            }
+        }
+        // the value is one of the variants, the one it fits best is the one it is
+        let variants = subschemas.any_of.iter().chain(&subschemas.one_of).flatten();
+        let best = variants
+            .filter_map(|variant| match variant {
+                Schema::Object(variant) if fits(value, variant) => {
+                    let mut found = vec![];
+                    unknown_keys(value, variant, definitions, path, &mut found);
+                    Some(found)
+                }
+                _ => None,
+            })
+            .min_by_key(Vec::len);
+        unknown.extend(best.into_iter().flatten());
+    }
+    match value {
+        Value::Object(map) => {
+            let Some(object) = &schema.object else {
+                return;
+            };
+            for (key, value) in map {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                match (
+                    object.properties.get(key),
+                    object.additional_properties.as_deref(),
+                ) {
+                    (Some(Schema::Object(property)), _)
+                    | (None, Some(Schema::Object(property))) => {
+                        unknown_keys(value, property, definitions, &path, unknown)
+                    }
+                    (None, Some(Schema::Bool(false))) => unknown.push(path),
+                    _ => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            let Some(SingleOrVec::Single(item)) =
+                schema.array.as_ref().and_then(|a| a.items.as_ref())
+            else {
+                return;
+            };
+            if let Schema::Object(item) = item.as_ref() {
+                for (i, value) in items.iter().enumerate() {
+                    unknown_keys(value, item, definitions, &format!("{path}[{i}]"), unknown);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA_FILE: &str = "./course.schema.json";
+
+    // The checked in schema is what the other services are written against,
+    // regenerate it with `rust_parser schema > course.schema.json`
+    #[test]
+    fn test_schema_file_is_up_to_date() {
+        let generated = serde_json::to_value(course_schema()).unwrap();
+        let file = std::fs::read_to_string(SCHEMA_FILE).unwrap();
+        let checked_in: serde_json::Value = serde_json::from_str(&file).unwrap();
+        assert!(
+            generated == checked_in,
+            "{SCHEMA_FILE} is out of date, regenerate it"
+        );
+    }
+
+    #[test]
+    fn test_validate_course() {
+        let parsed = crate::parser::parse_course(&crate::parser::test_page("LSLS10061U")).unwrap();
+        let json = serde_json::to_string(&parsed.output()).unwrap();
+        let record = validate_course(&json).unwrap();
+        assert_eq!(record.course, parsed.course);
+
+        let mut old: serde_json::Value = serde_json::from_str(&json).unwrap();
+        old.as_object_mut().unwrap().remove("schema_version");
+        assert!(validate_course(&old.to_string()).is_err());
+        old["schema_version"] = (SCHEMA_VERSION + 1).into();
+        assert!(validate_course(&old.to_string()).is_err());
+        assert!(read_course(&old.to_string()).is_err());
+        // a file from before the unknown departments can still be read, but it is not current
+        old["schema_version"] = 1.into();
+        assert!(validate_course(&old.to_string()).is_err());
+        assert_eq!(read_course(&old.to_string()).unwrap().course, parsed.course);
+        old["schema_version"] = SCHEMA_VERSION.into();
+        // the status was added later, the courses before it were all active
+        old.as_object_mut().unwrap().remove("status");
+        assert_eq!(
+            validate_course(&old.to_string()).unwrap().course,
+            parsed.course
+        );
+        old["logistics"]["faculty"] = serde_json::json!({"Unknown": "Faculty of Astrology"});
+        assert!(validate_course(&old.to_string()).is_ok());
+
+        // keys that the parser does not write, at the top where the course is flattened and inside it
+        for (field, key) in [
+            ("", "titel"),
+            ("/info", "ects_points"),
+            ("/logistics/coordinators/0", "phone"),
+            ("/logistics/faculty", "Known"),
+        ] {
+            let mut typo = old.clone();
+            typo.pointer_mut(field).unwrap()[key] = "x".into();
+            let error = validate_course(&typo.to_string()).unwrap_err().to_string();
+            assert!(error.contains(key), "{error}");
+        }
+        let mut raw = old.clone();
+        raw["raw_fields"]["Any label"] = "is allowed".into();
+        assert!(validate_course(&raw.to_string()).is_ok());
+        // the code has to match the pattern of the schema
+        for code in ["ndab15009u", " NDAB15009U", "ÜDAB15009U"] {
+            let mut wrong = old.clone();
+            wrong["info"]["id"] = code.into();
+            assert!(validate_course(&wrong.to_string()).is_err(), "{code}");
+        }
+        old["info"]["ects"] = "many".into();
+        assert!(validate_course(&old.to_string()).is_err());
+    }
+}
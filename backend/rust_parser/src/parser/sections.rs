@@ -0,0 +1,287 @@
+// Locates the parts of a course page that the field parsers read in a single walk over the dom,
+// `get_element_by_id` and `get_elements_by_class_name` walk every node of the page on each call
+use std::collections::HashMap;
+use tl::{HTMLTag, Node, Parser, VDom};
+
+// The classes that the field parsers look up
+const CLASSES: [&str; 3] = ["courseTitle", "alert", "panel-body"];
+
+// The elements that hold the content of the heading before them
+const BLOCKS: [&str; 4] = ["ul", "ol", "div", "p"];
+
+pub(crate) struct Sections<'a> {
+    pub parser: &'a Parser<'a>,
+    /// The page as it was read, for the heuristics that search the whole page
+    pub html: &'a str,
+    ids: HashMap<&'a [u8], &'a HTMLTag<'a>>,
+    classes: HashMap<&'static str, Vec<&'a HTMLTag<'a>>>,
+    /// The dl of the course information side-table, in the first panel-body with a dl
+    pub info_dl: Option<&'a HTMLTag<'a>>,
+    /// The panel-bodies with h5 headings, which hold the departments, faculty and coordinators
+    pub logistics: Vec<&'a HTMLTag<'a>>,
+}
+
+impl<'a> Sections<'a> {
+    pub fn new(dom: &'a VDom<'a>, html: &'a str) -> Self {
+        let parser = dom.parser();
+        let mut ids = HashMap::new();
+        let mut classes: HashMap<&'static str, Vec<&'a HTMLTag<'a>>> = HashMap::new();
+        for tag in dom.nodes().iter().filter_map(|node| node.as_tag()) {
+            let attributes = tag.attributes();
+            // like get_element_by_id the first element with an id wins
+            if let Some(id) = attributes.id() {
+                ids.entry(id.as_bytes()).or_insert(tag);
+            }
+            for class in CLASSES {
+                if attributes.is_class_member(class) {
+                    classes.entry(class).or_default().push(tag);
+                }
+            }
+        }
+
+        let panel_bodies = classes
+            .get("panel-body")
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let info_dl = panel_bodies.iter().find_map(|panel_body| {
+            panel_body
+                .query_selector(parser, "dl")?
+                .next()?
+                .get(parser)?
+                .as_tag()
+        });
+        let logistics = panel_bodies
+            .iter()
+            .filter(|panel_body| {
+                panel_body
+                    .query_selector(parser, "h5")
+                    .is_some_and(|mut h5s| h5s.next().is_some())
+            })
+            .copied()
+            .collect();
+
+        Self {
+            parser,
+            html,
+            ids,
+            classes,
+            info_dl,
+            logistics,
+        }
+    }
+
+    pub fn by_id(&self, id: &str) -> Option<&'a HTMLTag<'a>> {
+        self.ids.get(id.as_bytes()).copied()
+    }
+
+    /// The elements of one of the `CLASSES` in the order of the page
+    pub fn by_class(&self, class: &str) -> &[&'a HTMLTag<'a>] {
+        debug_assert!(CLASSES.contains(&class), "{class} is not located");
+        self.classes
+            .get(class)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+/// A heading and the content block that follows it, the items are the list items of the block
+/// or the block itself if it is not a list
+#[derive(Debug, Clone)]
+pub(crate) struct HeadedBlock<'a> {
+    pub heading: String,
+    pub items: Vec<BlockItem<'a>>,
+}
+
+/// A list item or a block that is not a list, with its text and its element for the parsers
+/// that read the links and attributes of the item
+#[derive(Debug, Clone)]
+pub(crate) struct BlockItem<'a> {
+    pub text: String,
+    pub tag: &'a HTMLTag<'a>,
+}
+
+/// Pairs every `heading` element in `container` with the content block (ul, ol, div or p) after it.
+/// The heading and its block only have to be siblings, text, comments and other elements between them
+/// are skipped, and the wrappers around headings and their blocks are walked into.
+/// A heading without a block has no items
+pub(crate) fn headed_blocks<'a>(
+    parser: &'a Parser<'a>,
+    container: &'a HTMLTag<'a>,
+    heading: &str,
+) -> Vec<HeadedBlock<'a>> {
+    let mut blocks = vec![];
+    walk_siblings(parser, container, heading, &mut blocks);
+    blocks
+}
+
+fn walk_siblings<'a>(
+    parser: &'a Parser<'a>,
+    parent: &'a HTMLTag<'a>,
+    heading: &str,
+    blocks: &mut Vec<HeadedBlock<'a>>,
+) {
+    // the heading that is still waiting for its block
+    let mut pending: Option<String> = None;
+    for child in parent.children().top().iter() {
+        let Some(tag) = child.get(parser).and_then(Node::as_tag) else {
+            continue;
+        };
+        let name = tag.name().as_utf8_str();
+        if name == heading {
+            close(pending.take(), vec![], blocks);
+            pending = Some(tag.inner_text(parser).to_string());
+        } else if contains(parser, tag, heading) {
+            close(pending.take(), vec![], blocks);
+            walk_siblings(parser, tag, heading, blocks);
+        } else if BLOCKS.contains(&name.as_ref()) && pending.is_some() {
+            close(pending.take(), items(parser, tag), blocks);
+        }
+    }
+    close(pending, vec![], blocks);
+}
+
+// Ends the block of a pending heading
+fn close<'a>(
+    pending: Option<String>,
+    items: Vec<BlockItem<'a>>,
+    blocks: &mut Vec<HeadedBlock<'a>>,
+) {
+    if let Some(heading) = pending {
+        blocks.push(HeadedBlock { heading, items });
+    }
+}
+
+fn contains(parser: &Parser, tag: &HTMLTag, name: &str) -> bool {
+    tag.query_selector(parser, name)
+        .is_some_and(|mut found| found.next().is_some())
+}
+
+fn items<'a>(parser: &'a Parser<'a>, block: &'a HTMLTag<'a>) -> Vec<BlockItem<'a>> {
+    let lis: Vec<BlockItem> = block
+        .query_selector(parser, "li")
+        .into_iter()
+        .flatten()
+        .filter_map(|li| {
+            let tag = li.get(parser)?.as_tag()?;
+            let text = tag.inner_text(parser).to_string();
+            Some(BlockItem { text, tag })
+        })
+        .collect();
+    if !lis.is_empty() {
+        return lis;
+    }
+    let text = block.inner_text(parser);
+    if text.trim().is_empty() {
+        vec![]
+    } else {
+        vec![BlockItem {
+            text: text.trim().to_string(),
+            tag: block,
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections_match_the_dom() {
+        let html = crate::parser::test_page("NDAB15009U");
+        let dom = tl::parse(&html, tl::ParserOptions::default()).unwrap();
+        let sections = Sections::new(&dom, &html);
+        let parser = dom.parser();
+
+        for id in [
+            "course-content",
+            "course-exams1",
+            "course-load",
+            "accordion-coursecontent",
+        ] {
+            let expected = dom.get_element_by_id(id).unwrap().get(parser).unwrap();
+            let found = sections.by_id(id).unwrap();
+            assert_eq!(found.raw(), expected.as_tag().unwrap().raw(), "{id}");
+        }
+        assert!(sections.by_id("no-such-id").is_none());
+        for class in CLASSES {
+            assert_eq!(
+                sections.by_class(class).len(),
+                dom.get_elements_by_class_name(class).count(),
+                "{class}"
+            );
+        }
+        assert!(sections.info_dl.is_some());
+        assert_eq!(sections.logistics.len(), 1);
+    }
+
+    fn blocks(html: &str) -> Vec<(String, Vec<String>)> {
+        let html = format!(r#"<div class="panel-body">{html}</div>"#);
+        let dom = tl::parse(&html, tl::ParserOptions::default()).unwrap();
+        let sections = Sections::new(&dom, &html);
+        headed_blocks(sections.parser, sections.by_class("panel-body")[0], "h5")
+            .into_iter()
+            .map(|block| {
+                let items = block.items.into_iter().map(|item| item.text).collect();
+                (block.heading, items)
+            })
+            .collect()
+    }
+
+    fn expected(pairs: &[(&str, &[&str])]) -> Vec<(String, Vec<String>)> {
+        pairs
+            .iter()
+            .map(|(heading, items)| {
+                let items = items.iter().map(|item| item.to_string()).collect();
+                (heading.to_string(), items)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_adjacent_lists() {
+        assert_eq!(
+            blocks("<div><a>Also for continuing education</a></div><h5>Contracting department</h5><ul><li>Department of Computer Science</li></ul><h5>Contracting faculty</h5><ul><li>Faculty of Science</li></ul><div class=\"last-modified\">Saved</div>"),
+            expected(&[
+                ("Contracting department", &["Department of Computer Science"]),
+                ("Contracting faculty", &["Faculty of Science"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_whitespace_and_comments() {
+        assert_eq!(
+            blocks("<h5>Course Coordinators</h5>\n  <!-- the coordinators -->\n  <ul>\n<li>A (a@ku.dk)</li>\n<li>B (b@ku.dk)</li>\n</ul>\n<h5>Contracting faculty</h5> <br> <ul><li>Faculty of Science</li></ul>"),
+            expected(&[
+                ("Course Coordinators", &["A (a@ku.dk)", "B (b@ku.dk)"]),
+                ("Contracting faculty", &["Faculty of Science"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_wrappers() {
+        // a wrapper around the list, and a wrapper around each heading and its list
+        assert_eq!(
+            blocks("<h5>Contracting department</h5><div class=\"list\"><ul><li>Department of Chemistry</li></ul></div><div><h5>Contracting faculty</h5><ul><li>Faculty of Science</li></ul></div><section><div><h5>Course Coordinators</h5><ol><li>A (a@ku.dk)</li></ol></div></section>"),
+            expected(&[
+                ("Contracting department", &["Department of Chemistry"]),
+                ("Contracting faculty", &["Faculty of Science"]),
+                ("Course Coordinators", &["A (a@ku.dk)"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_paragraphs_and_missing_blocks() {
+        assert_eq!(
+            blocks("<h5>Contracting faculty</h5><p> Faculty of Science </p><h5>Course Coordinators</h5><h5>Contracting department</h5><div>Department of Biology</div><h5>Study board</h5>"),
+            expected(&[
+                ("Contracting faculty", &["Faculty of Science"]),
+                ("Course Coordinators", &[]),
+                ("Contracting department", &["Department of Biology"]),
+                ("Study board", &[]),
+            ])
+        );
+    }
+}
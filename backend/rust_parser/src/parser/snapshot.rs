@@ -0,0 +1,109 @@
+// Every page in test_data/pages is parsed and compared to test_data/expected/{page}.json,
+// so the exact output change of a parser edit shows up in the diff of the snapshots.
+// Run the tests with UPDATE_SNAPSHOTS=1 to write the current output as the new snapshots
+use crate::manifest::{self, PARSER_VERSION};
+use crate::parser::{parse_course, ParsedCourse, TEST_HTMLS_DIR};
+use pretty_assertions::assert_eq;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const EXPECTED_DIR: &str = "./test_data/expected";
+// The parser version the snapshots were written by, and a hash of them
+const VERSION_FILE: &str = "./test_data/snapshot_version.json";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SnapshotVersion {
+    parser_version: String,
+    snapshots_hash: String,
+}
+
+// Pages that fail to parse are snapshotted too, a fix shows up as the error turning into a course
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Snapshot {
+    Parsed(Box<ParsedCourse>),
+    Error(String),
+}
+
+fn snapshot(html: &str) -> String {
+    let snapshot = match parse_course(html) {
+        Ok(parsed) => Snapshot::Parsed(Box::new(parsed)),
+        Err(error) => Snapshot::Error(format!("{error:#}")),
+    };
+    serde_json::to_string_pretty(&snapshot).unwrap() + "\n"
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem().unwrap().to_string_lossy().to_string()
+}
+
+#[test]
+fn test_snapshots() {
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let mut pages: Vec<_> = std::fs::read_dir(TEST_HTMLS_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    pages.sort();
+
+    let actuals: Vec<String> = pages
+        .iter()
+        .map(|page| snapshot(&std::fs::read_to_string(page).unwrap()))
+        .collect();
+    let version = SnapshotVersion {
+        parser_version: PARSER_VERSION.to_string(),
+        snapshots_hash: manifest::hash(actuals.concat().as_bytes()),
+    };
+    // the json directories are only parsed again by a new parser version, so an output change
+    // without a new version would leave the old output there
+    let recorded: SnapshotVersion =
+        serde_json::from_str(&std::fs::read_to_string(VERSION_FILE).unwrap()).unwrap();
+    assert!(
+        recorded.snapshots_hash == version.snapshots_hash
+            || recorded.parser_version != PARSER_VERSION,
+        "The output of the parser changed, bump PARSER_VERSION in src/manifest.rs"
+    );
+
+    let mut mismatches = vec![];
+    for (page, actual) in pages.iter().zip(actuals) {
+        let expected_path = Path::new(EXPECTED_DIR).join(format!("{}.json", stem(page)));
+        if update {
+            std::fs::create_dir_all(EXPECTED_DIR).unwrap();
+            std::fs::write(&expected_path, &actual).unwrap();
+            continue;
+        }
+        let expected = std::fs::read_to_string(&expected_path).unwrap_or_else(|_| {
+            panic!(
+                "No snapshot {}, run the tests with UPDATE_SNAPSHOTS=1 to create it",
+                expected_path.display()
+            )
+        });
+        if expected != actual {
+            mismatches.push((stem(page), expected, actual));
+        }
+    }
+
+    // a snapshot without a page is left over from a page that was removed
+    let stale: Vec<_> = std::fs::read_dir(EXPECTED_DIR)
+        .unwrap()
+        .map(|entry| stem(&entry.unwrap().path()))
+        .filter(|name| !pages.iter().any(|page| &stem(page) == name))
+        .collect();
+    assert!(stale.is_empty(), "Snapshots without a page: {stale:?}");
+
+    if let Some((page, expected, actual)) = mismatches.first() {
+        let pages: Vec<_> = mismatches.iter().map(|(page, ..)| page).collect();
+        eprintln!("Snapshots that changed: {pages:?}, run the tests with UPDATE_SNAPSHOTS=1 to accept the changes");
+        assert_eq!(expected, actual, "Snapshot of {page} changed");
+    }
+
+    if update {
+        let json = serde_json::to_string_pretty(&version).unwrap() + "\n";
+        std::fs::write(VERSION_FILE, json).unwrap();
+    } else {
+        assert_eq!(
+            recorded, version,
+            "The parser version changed, run the tests with UPDATE_SNAPSHOTS=1 to record it"
+        );
+    }
+}
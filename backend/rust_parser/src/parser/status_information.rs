@@ -0,0 +1,147 @@
+// Figuring out whether students can actually take the course
+use crate::parser::{CourseStatus, Diagnostics, Sections};
+
+// The title and alert boxes are short, so a few words are enough to tell the status,
+// cancelled courses are e.g. often renamed to "AFLYST - <title>".
+// The statuses are checked in order, so a cancelled course that is also closed is cancelled
+const MARKERS: [(CourseStatus, &[&str]); 3] = [
+    (
+        CourseStatus::Cancelled,
+        &["aflyst", "cancelled", "canceled"],
+    ),
+    (CourseStatus::NotOffered, &["udbydes ikke", "not offered"]),
+    (
+        CourseStatus::ClosedForRegistration,
+        &["lukket for tilmelding", "closed for registration"],
+    ),
+];
+
+// The notes and sign up sections are prose that often mentions that a course *may* be cancelled,
+// so we only trust whole sentences there
+const PHRASES: [(CourseStatus, &[&str]); 3] = [
+    (
+        CourseStatus::Cancelled,
+        &[
+            "kurset er aflyst",
+            "course is cancelled",
+            "course has been cancelled",
+            "course is canceled",
+        ],
+    ),
+    (
+        CourseStatus::NotOffered,
+        // anchored to when it is not offered, since e.g. "The course is not offered in English"
+        // or "Kurset udbydes ikke i kombination med ..." are notes on active courses
+        &[
+            "kurset udbydes ikke i år",
+            "kurset udbydes ikke i 20",
+            "kurset udbydes ikke i blok",
+            "course is not offered this year",
+            "course is not offered in 20",
+            "course is not offered in block",
+        ],
+    ),
+    (
+        CourseStatus::ClosedForRegistration,
+        &[
+            "lukket for tilmelding",
+            "tilmeldingen er lukket",
+            "closed for registration",
+            "closed for enrolment",
+            "registration is closed",
+        ],
+    ),
+];
+
+pub fn parse_course_status(page: &Sections, diagnostics: &mut Diagnostics) -> CourseStatus {
+    let parser = page.parser;
+    let mut headings: Vec<(&str, String)> = Vec::new();
+    let mut sections: Vec<(&str, String)> = Vec::new();
+
+    for class in ["courseTitle", "alert"] {
+        for tag in page.by_class(class) {
+            headings.push((class, tag.inner_text(parser).to_string()));
+        }
+    }
+    for id in ["course-notes", "course-signup"] {
+        if let Some(tag) = page.by_id(id) {
+            sections.push((id, tag.inner_text(parser).to_string()));
+        }
+    }
+
+    for ((status, markers), (_, phrases)) in MARKERS.iter().zip(PHRASES.iter()) {
+        let candidates = headings
+            .iter()
+            .map(|heading| (heading, markers))
+            .chain(sections.iter().map(|section| (section, phrases)));
+        for ((section, text), phrases) in candidates {
+            let lowercase = text.to_lowercase();
+            if let Some(phrase) = phrases.iter().find(|p| lowercase.contains(*p)) {
+                diagnostics.trace("status", section, phrase, text.trim());
+                return status.clone();
+            }
+        }
+    }
+    CourseStatus::Active
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(html: &str) -> CourseStatus {
+        let dom = tl::parse(html, tl::ParserOptions::default()).unwrap();
+        parse_course_status(&Sections::new(&dom, html), &mut Diagnostics::default())
+    }
+
+    #[test]
+    fn test_course_status() {
+        assert_eq!(
+            status(r#"<h1 class="courseTitle">NDAB15009U AFLYST - Datalogi</h1>"#),
+            CourseStatus::Cancelled
+        );
+        assert_eq!(
+            status(r#"<div class="alert alert-info">The course is not offered in 2024/2025</div>"#),
+            CourseStatus::NotOffered
+        );
+        assert_eq!(
+            status(r#"<div id="course-signup"><p>Kurset er lukket for tilmelding.</p></div>"#),
+            CourseStatus::ClosedForRegistration
+        );
+        assert_eq!(
+            status(
+                r#"<div id="course-signup">Registration is closed</div><div class="alert">Cancelled</div>"#
+            ),
+            CourseStatus::Cancelled
+        );
+        assert_eq!(
+            status(r#"<h1 class="courseTitle">NDAB15009U Datalogi</h1>"#),
+            CourseStatus::Active
+        );
+        // a warning about a possible cancellation is not a cancellation
+        assert_eq!(
+            status(
+                r#"<div id="course-signup">The course may be cancelled if fewer than 10 students sign up</div>"#
+            ),
+            CourseStatus::Active
+        );
+        assert_eq!(
+            status(r#"<div id="course-notes">Kurset udbydes ikke i blok 3 2024/2025.</div>"#),
+            CourseStatus::NotOffered
+        );
+    }
+
+    #[test]
+    fn test_active_course_with_language_note() {
+        let html = crate::parser::test_page("LSLS10061U");
+        for note in [
+            "The course is not offered in English.",
+            "Kurset udbydes ikke i kombination med NIGK19001U.",
+        ] {
+            let noted = html.replace("Kurset foregår på", &format!("{note} Kurset foregår på"));
+            assert_ne!(noted, html);
+            let course = crate::parser::parse_course(&noted).unwrap().course;
+            assert_eq!(course.status, CourseStatus::Active, "{note}");
+        }
+    }
+}
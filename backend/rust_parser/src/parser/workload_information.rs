@@ -1,32 +1,31 @@
-use crate::parser::{Workload, WorkloadType};
+use crate::parser::{Diagnostics, ParseError, Sections, Workload, WorkloadType};
 use anyhow::{ensure, Context, Result};
 
-use tl::VDom;
+pub fn parse_workloads(page: &Sections, diagnostics: &mut Diagnostics) -> Result<Vec<Workload>> {
+    let parser = page.parser;
+    let workload_table = page
+        .by_id("course-load")
+        .ok_or(ParseError::Missing { field: "workloads" })?;
 
-pub fn parse_workloads(dom: &VDom) -> Result<Vec<Workload>> {
-    let parser = dom.parser();
-    let workload_table = dom
-        .get_element_by_id("course-load")
-        .context("Unable to find workload table")?
-        .get(parser)
-        .unwrap()
-        .as_tag()
-        .unwrap();
-
-    let mut lis = workload_table
+    let lis = workload_table
         .query_selector(parser, "li")
         .context("Unable to find any workload information")?
-        .map(|node| node.get(parser).unwrap().inner_text(parser))
-        .skip(2)
-        .peekable();
+        .filter_map(|node| Some(node.get(parser)?.inner_text(parser)))
+        .skip(2);
 
     let mut workloads = Vec::new();
     // take two at a time from lis
     // first is the workload type
     // second is the workload value
     let mut pair: Vec<String> = Vec::new();
-    while lis.peek().is_some() {
+    for li in lis {
         if pair.len() == 2 {
+            diagnostics.trace(
+                &format!("workloads[{}]", workloads.len()),
+                "course-load",
+                &pair[0],
+                &pair[1],
+            );
             let workload = Workload {
                 workload_type: WorkloadType::from_str(&pair[0])?,
                 hours: pair[1]
@@ -37,7 +36,7 @@ pub fn parse_workloads(dom: &VDom) -> Result<Vec<Workload>> {
             workloads.push(workload);
             pair.clear();
         }
-        pair.push(lis.next().unwrap().to_string());
+        pair.push(li.to_string());
     }
 
     ensure!(
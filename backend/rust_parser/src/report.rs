@@ -0,0 +1,281 @@
+// Groups the pages that failed to parse by what went wrong,
+// so that parser fixes can be ranked by how many courses they bring back
+use crate::parser::ParseError;
+use crate::validation::{Finding, Severity};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+// How many course ids are kept as examples of a group or value
+const EXAMPLES: usize = 5;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailureReport {
+    pub failures: usize,
+    /// Sorted by how many courses failed in each group, most first
+    pub groups: Vec<FailureGroup>,
+    /// The findings of the validation of the courses that did parse, the most severe first
+    #[serde(default)]
+    pub findings: Vec<FindingGroup>,
+    /// The most severe finding of every course that has any, a course can be in several groups
+    #[serde(default)]
+    pub flagged: BTreeMap<String, Severity>,
+}
+
+/// The failures of a kind of error (see `ParseError::kind`) on a field,
+/// errors that are not a `ParseError` are of the kind "other" and have no field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailureGroup {
+    pub kind: String,
+    pub field: Option<String>,
+    pub count: usize,
+    pub examples: Vec<String>,
+    /// The unknown values of the field, or the error messages for the kind "other"
+    pub values: Vec<ValueCount>,
+}
+
+/// The courses that a validation rule found something questionable in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FindingGroup {
+    pub severity: Severity,
+    pub rule: String,
+    pub count: usize,
+    pub examples: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValueCount {
+    pub value: String,
+    pub count: usize,
+    pub examples: Vec<String>,
+}
+
+impl FailureReport {
+    /// Adds the failure of a page, `course_id` is whatever the page is known by (usually its file name)
+    pub fn add(&mut self, course_id: &str, error: &anyhow::Error) {
+        let (kind, field, value) = match ParseError::of(error) {
+            Some(e @ ParseError::UnknownValue { field, value }) => {
+                (e.kind(), Some(*field), Some(value.clone()))
+            }
+            Some(e @ ParseError::Missing { field }) => (e.kind(), Some(*field), None),
+            None => ("other", None, Some(error.root_cause().to_string())),
+        };
+        self.failures += 1;
+
+        let position = self
+            .groups
+            .iter()
+            .position(|g| g.kind == kind && g.field.as_deref() == field);
+        let group = match position {
+            Some(i) => &mut self.groups[i],
+            None => {
+                self.groups.push(FailureGroup {
+                    kind: kind.to_string(),
+                    field: field.map(str::to_string),
+                    count: 0,
+                    examples: Vec::new(),
+                    values: Vec::new(),
+                });
+                self.groups.last_mut().unwrap()
+            }
+        };
+        group.count += 1;
+        add_example(&mut group.examples, course_id);
+
+        if let Some(value) = value {
+            match group.values.iter_mut().find(|v| v.value == value) {
+                Some(existing) => {
+                    existing.count += 1;
+                    add_example(&mut existing.examples, course_id);
+                }
+                None => group.values.push(ValueCount {
+                    value,
+                    count: 1,
+                    examples: vec![course_id.to_string()],
+                }),
+            }
+            group
+                .values
+                .sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        }
+        self.groups.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| (&a.kind, &a.field).cmp(&(&b.kind, &b.field)))
+        });
+    }
+
+    /// Adds the validation findings of a parsed course, a rule is counted once per course
+    pub fn add_findings(&mut self, course_id: &str, findings: &[Finding]) {
+        let mut counted: Vec<(Severity, &str)> = vec![];
+        for finding in findings {
+            let key = (finding.severity, finding.rule.as_str());
+            if counted.contains(&key) {
+                continue;
+            }
+            counted.push(key);
+            self.flagged
+                .entry(course_id.to_string())
+                .and_modify(|severity| *severity = (*severity).max(finding.severity))
+                .or_insert(finding.severity);
+            match self
+                .findings
+                .iter_mut()
+                .find(|g| g.severity == finding.severity && g.rule == finding.rule)
+            {
+                Some(group) => {
+                    group.count += 1;
+                    add_example(&mut group.examples, course_id);
+                }
+                None => self.findings.push(FindingGroup {
+                    severity: finding.severity,
+                    rule: finding.rule.clone(),
+                    count: 1,
+                    examples: vec![course_id.to_string()],
+                }),
+            }
+        }
+        self.findings.sort_by(|a, b| {
+            (b.severity, b.count)
+                .cmp(&(a.severity, a.count))
+                .then_with(|| a.rule.cmp(&b.rule))
+        });
+    }
+
+    /// How many courses have a finding of at least this severity
+    pub fn findings_at_least(&self, severity: Severity) -> usize {
+        self.flagged.values().filter(|s| **s >= severity).count()
+    }
+}
+
+fn add_example(examples: &mut Vec<String>, course_id: &str) {
+    if examples.len() < EXAMPLES {
+        examples.push(course_id.to_string());
+    }
+}
+
+// The readable summary of the report
+impl fmt::Display for FailureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} pages failed to parse", self.failures)?;
+        for group in &self.groups {
+            let field = group.field.as_deref().unwrap_or("-");
+            writeln!(
+                f,
+                "{:>6} {} {field}, e.g. {}",
+                group.count,
+                group.kind,
+                group.examples.join(", ")
+            )?;
+            for value in &group.values {
+                writeln!(
+                    f,
+                    "{:>12} {:?}, e.g. {}",
+                    value.count,
+                    value.value,
+                    value.examples.join(", ")
+                )?;
+            }
+        }
+        for group in &self.findings {
+            writeln!(
+                f,
+                "{:>6} {} {}, e.g. {}",
+                group.count,
+                group.severity,
+                group.rule,
+                group.examples.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    fn unknown(field: &'static str, value: &str) -> anyhow::Error {
+        // the typed error is found through the context that parse_course adds
+        Err::<(), _>(ParseError::unknown(field, value))
+            .context("Unable to parse course: Some title")
+            .unwrap_err()
+    }
+
+    #[test]
+    fn test_groups_failures() {
+        let mut report = FailureReport::default();
+        report.add(
+            "A",
+            &unknown("logistics.departments", "Department of Nowhere"),
+        );
+        report.add(
+            "B",
+            &unknown("logistics.departments", "Department of Nowhere"),
+        );
+        report.add(
+            "C",
+            &unknown("logistics.departments", "Department of Elsewhere"),
+        );
+        report.add("D", &unknown("info.duration", "30 June-5 July"));
+        report.add("E", &anyhow::anyhow!("Odd number of elements in dl"));
+
+        assert_eq!(report.failures, 5);
+        let departments = &report.groups[0];
+        assert_eq!(departments.field.as_deref(), Some("logistics.departments"));
+        assert_eq!(departments.count, 3);
+        assert_eq!(
+            departments.values[0],
+            ValueCount {
+                value: "Department of Nowhere".into(),
+                count: 2,
+                examples: vec!["A".into(), "B".into()],
+            }
+        );
+        // groups of the same size are sorted by kind and field
+        assert_eq!(report.groups[1].kind, "other");
+        assert_eq!(
+            report.groups[1].values[0].value,
+            "Odd number of elements in dl"
+        );
+        assert_eq!(report.groups[2].field.as_deref(), Some("info.duration"));
+    }
+
+    #[test]
+    fn test_groups_findings() {
+        let finding = |severity, rule: &str| Finding {
+            severity,
+            rule: rule.to_string(),
+            field: "exams[0]".into(),
+            message: String::new(),
+        };
+        let mut report = FailureReport::default();
+        let twice = finding(Severity::Warning, "exam_duration");
+        report.add_findings("A", &[twice.clone(), twice]);
+        report.add_findings("B", &[finding(Severity::Warning, "exam_duration")]);
+        report.add_findings("C", &[finding(Severity::Error, "workload_hours")]);
+        // a course that trips several rules is one course
+        report.add_findings(
+            "D",
+            &[
+                finding(Severity::Warning, "exam_duration"),
+                finding(Severity::Error, "workload_hours"),
+            ],
+        );
+
+        assert_eq!(report.failures, 0);
+        assert_eq!(report.findings[0].rule, "workload_hours");
+        assert_eq!(
+            report.findings[1],
+            FindingGroup {
+                severity: Severity::Warning,
+                rule: "exam_duration".into(),
+                count: 3,
+                examples: vec!["A".into(), "B".into(), "D".into()],
+            }
+        );
+        assert_eq!(report.findings_at_least(Severity::Warning), 4);
+        assert_eq!(report.findings_at_least(Severity::Error), 2);
+    }
+}
@@ -0,0 +1,342 @@
+// The places the parsed courses can be written to besides a directory with a json file per course
+use crate::parser::{Course, CourseCode, ParsedCourse};
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Something the parsed courses are written to one at a time, `finish` has to be called
+/// once every course is written
+pub trait CourseSink {
+    fn write(&mut self, parsed: &ParsedCourse) -> Result<()>;
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// A directory with a `{id}.json` file per course
+pub struct JsonDirSink<'a> {
+    dir: &'a Path,
+    pretty: bool,
+}
+
+impl<'a> JsonDirSink<'a> {
+    pub fn new(dir: &'a Path, pretty: bool) -> Self {
+        Self { dir, pretty }
+    }
+
+    pub fn path(&self, id: &CourseCode) -> PathBuf {
+        self.dir.join(format!("{}.json", id.as_str()))
+    }
+}
+
+impl CourseSink for JsonDirSink<'_> {
+    fn write(&mut self, parsed: &ParsedCourse) -> Result<()> {
+        let output = parsed.output();
+        let json = if self.pretty {
+            serde_json::to_string_pretty(&output)?
+        } else {
+            serde_json::to_string(&output)?
+        };
+        let path = self.path(&parsed.course.info.id);
+        std::fs::write(&path, json).with_context(|| format!("Could not write {}", path.display()))
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A single stream with the json of a course on every line
+pub struct NdjsonSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> NdjsonSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> CourseSink for NdjsonSink<W> {
+    fn write(&mut self, parsed: &ParsedCourse) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, &parsed.output())?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+// One row per course, and a table per list in the course so it can be queried without json functions.
+// Enums are stored by their variant name, the value of variants like `Schedule::Other` goes in `detail`
+const SQLITE_SCHEMA: &str = "
+CREATE TABLE course (
+    id TEXT PRIMARY KEY,
+    schema_version INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    english_title TEXT,
+    status TEXT NOT NULL,
+    ects REAL NOT NULL,
+    duration TEXT NOT NULL,
+    duration_detail TEXT,
+    capacity INTEGER,
+    faculty TEXT NOT NULL,
+    content TEXT NOT NULL,
+    learning_outcome TEXT NOT NULL,
+    recommended_qualifications TEXT,
+    summary TEXT NOT NULL
+);
+CREATE TABLE course_block (course_id TEXT NOT NULL REFERENCES course(id), block TEXT NOT NULL, detail TEXT);
+CREATE TABLE course_schedule (course_id TEXT NOT NULL REFERENCES course(id), schedule TEXT NOT NULL, detail TEXT);
+CREATE TABLE course_language (course_id TEXT NOT NULL REFERENCES course(id), language TEXT NOT NULL);
+CREATE TABLE course_degree (course_id TEXT NOT NULL REFERENCES course(id), degree TEXT NOT NULL);
+CREATE TABLE course_department (course_id TEXT NOT NULL REFERENCES course(id), department TEXT NOT NULL);
+CREATE TABLE exam (
+    course_id TEXT NOT NULL REFERENCES course(id),
+    position INTEGER NOT NULL,
+    exam_type TEXT NOT NULL,
+    minutes INTEGER
+);
+CREATE TABLE workload (
+    course_id TEXT NOT NULL REFERENCES course(id),
+    workload_type TEXT NOT NULL,
+    hours REAL NOT NULL
+);
+CREATE TABLE coordinator (
+    email TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    given_name TEXT NOT NULL,
+    family_name TEXT NOT NULL,
+    email_kind TEXT
+);
+CREATE TABLE course_coordinator (
+    course_id TEXT NOT NULL REFERENCES course(id),
+    email TEXT NOT NULL REFERENCES coordinator(email)
+);
+CREATE TABLE raw_field (
+    course_id TEXT NOT NULL REFERENCES course(id),
+    label TEXT NOT NULL,
+    value TEXT NOT NULL
+);
+";
+
+/// A SQLite database with normalised tables, the database is created from scratch
+/// and every course is written in a single transaction
+pub struct SqliteSink {
+    connection: Connection,
+}
+
+impl SqliteSink {
+    pub fn create(path: &Path) -> Result<Self> {
+        if path.exists() {
+            std::fs::remove_file(path)
+                .with_context(|| format!("Could not replace {}", path.display()))?;
+        }
+        let connection = Connection::open(path)?;
+        Self::with_connection(connection)
+    }
+
+    pub fn with_connection(connection: Connection) -> Result<Self> {
+        connection.execute_batch(SQLITE_SCHEMA)?;
+        connection.execute_batch("BEGIN")?;
+        Ok(Self { connection })
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    fn insert(&self, course: &Course) -> Result<()> {
+        let db = &self.connection;
+        let id = course.info.id.as_str();
+        let (duration, duration_detail) = variant(&course.info.duration);
+        // an unknown faculty is stored with its name as on the page
+        let (faculty, unknown) = variant(&course.logistics.faculty);
+        let faculty = unknown.unwrap_or(faculty);
+        let description = &course.description;
+        db.execute(
+            "INSERT INTO course VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            params![
+                id,
+                crate::parser::SCHEMA_VERSION,
+                course.title,
+                course.english_title,
+                variant(&course.status).0,
+                course.info.ects,
+                duration,
+                duration_detail,
+                course.info.capacity.0,
+                faculty,
+                description.content,
+                description.learning_outcome,
+                description.recommended_qualifications,
+                description.summary,
+            ],
+        )?;
+
+        for block in &course.info.block {
+            let (block, detail) = variant(block);
+            db.execute(
+                "INSERT INTO course_block VALUES (?1, ?2, ?3)",
+                params![id, block, detail],
+            )?;
+        }
+        for schedule in &course.info.schedule {
+            let (schedule, detail) = variant(schedule);
+            db.execute(
+                "INSERT INTO course_schedule VALUES (?1, ?2, ?3)",
+                params![id, schedule, detail],
+            )?;
+        }
+        for language in &course.info.language {
+            db.execute(
+                "INSERT INTO course_language VALUES (?1, ?2)",
+                params![id, variant(language).0],
+            )?;
+        }
+        for degree in &course.info.degree {
+            db.execute(
+                "INSERT INTO course_degree VALUES (?1, ?2)",
+                params![id, variant(degree).0],
+            )?;
+        }
+        for department in &course.logistics.departments {
+            // an unknown department is stored with its name as on the page
+            let (name, unknown) = variant(department);
+            db.execute(
+                "INSERT INTO course_department VALUES (?1, ?2)",
+                params![id, unknown.unwrap_or(name)],
+            )?;
+        }
+        for (position, exam) in course.exams.iter().enumerate() {
+            let (exam_type, minutes) = variant(exam);
+            db.execute(
+                "INSERT INTO exam VALUES (?1, ?2, ?3, ?4)",
+                params![id, position, exam_type, minutes],
+            )?;
+        }
+        for workload in &course.workloads {
+            db.execute(
+                "INSERT INTO workload VALUES (?1, ?2, ?3)",
+                params![id, variant(&workload.workload_type).0, workload.hours],
+            )?;
+        }
+        for coordinator in &course.logistics.coordinators {
+            // coordinators are shared between courses, the first name we see is kept
+            db.execute(
+                "INSERT OR IGNORE INTO coordinator VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    coordinator.email,
+                    coordinator.name,
+                    coordinator.given_name,
+                    coordinator.family_name,
+                    coordinator.email_kind.as_ref().map(|kind| variant(kind).0),
+                ],
+            )?;
+            db.execute(
+                "INSERT INTO course_coordinator VALUES (?1, ?2)",
+                params![id, coordinator.email],
+            )?;
+        }
+        for (label, value) in &course.raw_fields {
+            db.execute(
+                "INSERT INTO raw_field VALUES (?1, ?2, ?3)",
+                params![id, label, value],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl CourseSink for SqliteSink {
+    fn write(&mut self, parsed: &ParsedCourse) -> Result<()> {
+        self.insert(&parsed.course)
+            .with_context(|| format!("Could not insert {}", parsed.course.info.id))
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.connection.execute_batch("COMMIT")?;
+        Ok(())
+    }
+}
+
+// The variant name of an enum and the value it holds as text, if any.
+// `Exam::Oral(Some(30))` is ("Oral", Some("30")), and `Block::One` is ("One", None)
+fn variant<T: Serialize>(value: &T) -> (String, Option<String>) {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => (name, None),
+        Ok(serde_json::Value::Object(map)) => match map.into_iter().next() {
+            Some((name, serde_json::Value::Null)) => (name, None),
+            Some((name, serde_json::Value::String(detail))) => (name, Some(detail)),
+            Some((name, detail)) => (name, Some(detail.to_string())),
+            None => (String::new(), None),
+        },
+        Ok(other) => (other.to_string(), None),
+        Err(_) => (String::new(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_course, test_page, Block, Exam, Schedule};
+
+    #[test]
+    fn test_variant() {
+        assert_eq!(variant(&Block::One), ("One".to_string(), None));
+        assert_eq!(
+            variant(&Schedule::Other("Summer course".into())),
+            ("Other".to_string(), Some("Summer course".to_string()))
+        );
+        assert_eq!(
+            variant(&Exam::Oral(Some(30))),
+            ("Oral".to_string(), Some("30".to_string()))
+        );
+        assert_eq!(variant(&Exam::Written(None)), ("Written".to_string(), None));
+    }
+
+    #[test]
+    fn test_sqlite_sink() {
+        let parsed = parse_course(&test_page("LSLS10061U")).unwrap();
+        let mut sink =
+            Box::new(SqliteSink::with_connection(Connection::open_in_memory().unwrap()).unwrap());
+        sink.write(&parsed).unwrap();
+        let db = sink.connection();
+        let (title, ects): (String, f64) = db
+            .query_row(
+                "SELECT title, ects FROM course WHERE id = 'LSLS10061U'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            (title.as_str(), ects),
+            ("International Naturforvaltning", 7.5)
+        );
+        let exams: Vec<(String, Option<i64>)> = db
+            .prepare("SELECT exam_type, minutes FROM exam ORDER BY position")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(exams, vec![("Oral".to_string(), Some(30))]);
+        sink.finish().unwrap();
+    }
+
+    #[test]
+    fn test_ndjson_sink() {
+        let parsed = parse_course(&test_page("LSLS10061U")).unwrap();
+        let mut out = Vec::new();
+        let mut sink = NdjsonSink::new(&mut out);
+        sink.write(&parsed).unwrap();
+        sink.write(&parsed).unwrap();
+        let lines: Vec<_> = std::str::from_utf8(&out).unwrap().lines().collect();
+        assert_eq!(lines.len(), 2);
+        let record = crate::parser::schema::validate_course(lines[0]).unwrap();
+        assert_eq!(record.course, parsed.course);
+    }
+}
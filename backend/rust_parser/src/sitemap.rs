@@ -0,0 +1,300 @@
+// Decides which pages have to be fetched, parsed again or deleted, by comparing the sitemap of
+// the course catalogue with the pages directory and the manifest of the json directory
+use crate::manifest::{hash, Manifest};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+pub const SITEMAP_URL: &str = "https://kurser.ku.dk/sitemap.xml";
+
+/// A course page in the sitemap, the page is saved as `{id}.html`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SitemapEntry {
+    pub loc: String,
+    pub id: String,
+    /// The `lastmod` of the sitemap, a W3C date (and time)
+    pub lastmod: Option<String>,
+}
+
+impl SitemapEntry {
+    pub fn page(&self) -> String {
+        format!("{}.html", self.id)
+    }
+
+    // The start of the `lastmod` day in Copenhagen, the time of day is left out like the old scraper did
+    fn modified(&self) -> Option<SystemTime> {
+        let date = self.lastmod.as_deref()?.get(..10)?;
+        let mut parts = date.split('-').map(str::parse::<i64>);
+        let (year, month, day) = (
+            parts.next()?.ok()?,
+            parts.next()?.ok()?,
+            parts.next()?.ok()?,
+        );
+        let days = days_from_civil(year, month, day);
+        let seconds = days * 24 * 60 * 60 - copenhagen_offset(year, days);
+        Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds.try_into().ok()?))
+    }
+}
+
+// The offset from UTC in seconds at the start of a day in Copenhagen. Summer time starts and ends
+// at 01:00 UTC on the last sunday of March and October, so a day that it starts on begins in winter
+// time and a day that it ends on begins in summer time
+fn copenhagen_offset(year: i64, days: i64) -> i64 {
+    let last_sunday = |month| {
+        let last = days_from_civil(year, month, 31);
+        // 1970-01-01 was a thursday
+        last - (last + 4).rem_euclid(7)
+    };
+    let hours = if (last_sunday(3) + 1..=last_sunday(10)).contains(&days) {
+        2
+    } else {
+        1
+    };
+    hours * 60 * 60
+}
+
+// Days since 1970-01-01 of a date in the proleptic gregorian calendar
+// (http://howardhinnant.github.io/date_algorithms.html#days_from_civil)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Reads the sitemap from a file, or fetches it if `source` is a url
+pub fn load_sitemap(source: &str) -> Result<String> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let response = ureq::get(source)
+            .call()
+            .with_context(|| format!("Could not fetch {source}"))?;
+        Ok(response.into_string()?)
+    } else {
+        std::fs::read_to_string(source).with_context(|| format!("Could not read {source}"))
+    }
+}
+
+/// The course pages of a sitemap, the url of the catalogue itself is left out
+pub fn parse_sitemap(xml: &str) -> Result<Vec<SitemapEntry>> {
+    let document = roxmltree::Document::parse(xml).context("Invalid sitemap xml")?;
+    let root = document.root_element();
+    if root.tag_name().name() != "urlset" {
+        bail!(
+            "Expected a urlset sitemap, found {}",
+            root.tag_name().name()
+        );
+    }
+
+    let mut entries = vec![];
+    for url in root.children().filter(|node| node.has_tag_name("url")) {
+        let text = |name: &str| {
+            url.children()
+                .find(|node| node.tag_name().name() == name)
+                .and_then(|node| node.text())
+                .map(|text| text.trim().to_string())
+        };
+        let Some(loc) = text("loc") else {
+            continue;
+        };
+        // the page is named after the last segment of the path, the catalogue itself has no path
+        let path = loc.split("://").last().unwrap_or_default();
+        let Some(id) = path.split('/').skip(1).filter(|s| !s.is_empty()).last() else {
+            continue;
+        };
+        entries.push(SitemapEntry {
+            id: id.to_string(),
+            lastmod: text("lastmod"),
+            loc,
+        });
+    }
+    Ok(entries)
+}
+
+/// What has to happen to bring the pages directory and the json directory up to date with the sitemap
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plan {
+    /// Pages that are missing or were modified after they were saved
+    pub fetch: Vec<SitemapEntry>,
+    /// Pages that are up to date but were not parsed by this version of the parser,
+    /// the fetched pages have to be parsed as well
+    pub reparse: Vec<String>,
+    /// Pages that are no longer in the sitemap
+    pub delete: Vec<String>,
+}
+
+pub fn plan(entries: &[SitemapEntry], pages_dir: &Path, manifest: &Manifest) -> Result<Plan> {
+    let mut plan = Plan::default();
+    for entry in entries {
+        let path = pages_dir.join(entry.page());
+        let saved = match std::fs::metadata(&path) {
+            Ok(metadata) => metadata.modified()?,
+            Err(_) => {
+                plan.fetch.push(entry.clone());
+                continue;
+            }
+        };
+        if entry.modified().is_some_and(|modified| modified > saved) {
+            plan.fetch.push(entry.clone());
+            continue;
+        }
+        let bytes =
+            std::fs::read(&path).with_context(|| format!("Could not read {}", path.display()))?;
+        if manifest.current(&entry.page(), &hash(&bytes)).is_none() {
+            plan.reparse.push(entry.page());
+        }
+    }
+
+    let pages: BTreeSet<_> = entries.iter().map(SitemapEntry::page).collect();
+    for dir_entry in std::fs::read_dir(pages_dir)
+        .with_context(|| format!("Could not read {}", pages_dir.display()))?
+    {
+        let name = dir_entry?.file_name().to_string_lossy().into_owned();
+        // hidden files are not pages, e.g. the temporary files of a fetch
+        if !name.starts_with('.') && !pages.contains(&name) {
+            plan.delete.push(name);
+        }
+    }
+    plan.delete.sort();
+    Ok(plan)
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.fetch.is_empty() && self.reparse.is_empty() && self.delete.is_empty()
+    }
+}
+
+// The readable summary of the plan
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} to fetch, {} to parse again, {} to delete",
+            self.fetch.len(),
+            self.reparse.len(),
+            self.delete.len()
+        )?;
+        for entry in &self.fetch {
+            writeln!(f, "fetch: {}", entry.loc)?;
+        }
+        for page in &self.reparse {
+            writeln!(f, "reparse: {page}")?;
+        }
+        for page in &self.delete {
+            writeln!(f, "delete: {page}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::{ManifestEntry, PARSER_VERSION};
+
+    fn fixture() -> Vec<SitemapEntry> {
+        let xml = std::fs::read_to_string("./test_data/sitemaps/sitemap.xml").unwrap();
+        parse_sitemap(&xml).unwrap()
+    }
+
+    #[test]
+    fn test_parse_sitemap() {
+        let entries = fixture();
+        let ids: Vec<_> = entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            ["LSLS10061U", "NDAB15009U", "NDAB22007U", "NMAA04032U"]
+        );
+        assert_eq!(
+            entries[0],
+            SitemapEntry {
+                loc: "https://kurser.ku.dk/course/LSLS10061U".into(),
+                id: "LSLS10061U".into(),
+                lastmod: Some("2023-04-25".into()),
+            }
+        );
+        assert_eq!(entries[3].lastmod, None);
+        assert!(parse_sitemap("<sitemapindex></sitemapindex>").is_err());
+        assert!(parse_sitemap("<urlset>").is_err());
+    }
+
+    #[test]
+    fn test_modified() {
+        let seconds = |entry: &SitemapEntry| {
+            entry
+                .modified()
+                .map(|t| t.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs())
+        };
+        let entries = fixture();
+        // midnight in Copenhagen, in summer time and in winter time
+        assert_eq!(seconds(&entries[0]), Some(19472 * 86400 - 2 * 3600));
+        assert_eq!(seconds(&entries[2]), Some(19681 * 86400 - 3600));
+        assert_eq!(seconds(&entries[3]), None);
+        let midnight = |date: &str| {
+            let entry = SitemapEntry {
+                loc: String::new(),
+                id: String::new(),
+                lastmod: Some(date.into()),
+            };
+            seconds(&entry).unwrap() % 86400
+        };
+        // summer time in 2023 started on 26 March and ended on 29 October
+        assert_eq!(midnight("2023-03-26"), 23 * 3600);
+        assert_eq!(midnight("2023-03-27"), 22 * 3600);
+        assert_eq!(midnight("2023-10-29"), 22 * 3600);
+        assert_eq!(midnight("2023-10-30"), 23 * 3600);
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+    }
+
+    #[test]
+    fn test_plan() {
+        let dir = std::env::temp_dir().join(format!("rust_parser_plan_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let day = |date: &str| {
+            let entry = SitemapEntry {
+                loc: String::new(),
+                id: String::new(),
+                lastmod: Some(date.into()),
+            };
+            entry.modified().unwrap()
+        };
+        let save = |page: &str, content: &str, saved: SystemTime| {
+            let file = std::fs::File::create(dir.join(page)).unwrap();
+            std::io::Write::write_all(&mut &file, content.as_bytes()).unwrap();
+            file.set_modified(saved).unwrap();
+        };
+        // saved after it was last modified and parsed by this version
+        save("LSLS10061U.html", "a", day("2023-05-01"));
+        // saved after it was last modified but never parsed
+        save("NMAA04032U.html", "b", day("2023-05-01"));
+        // modified after it was saved
+        save("NDAB15009U.html", "c", day("2023-05-01"));
+        // no longer in the sitemap
+        save("NBIK15000U.html", "d", day("2023-05-01"));
+        save(".NDAB22007U.html.tmp", "e", day("2023-05-01"));
+
+        let mut manifest = Manifest::default();
+        manifest.entries.insert(
+            "LSLS10061U.html".into(),
+            ManifestEntry {
+                source_hash: hash(b"a"),
+                parser_version: PARSER_VERSION.into(),
+                course_id: Some("LSLS10061U".parse().unwrap()),
+                output_hash: Some(hash(b"{}")),
+            },
+        );
+
+        let plan = plan(&fixture(), &dir, &manifest).unwrap();
+        let fetched: Vec<_> = plan.fetch.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(fetched, ["NDAB15009U", "NDAB22007U"]);
+        assert_eq!(plan.reparse, ["NMAA04032U.html"]);
+        assert_eq!(plan.delete, ["NBIK15000U.html"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
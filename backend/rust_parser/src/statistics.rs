@@ -0,0 +1,284 @@
+// Parses the histogram pages of karakterstatistik.stads.ku.dk into the grade distribution of the exam
+// and the re-exam, with the same statistics as the db-manager computes for them
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tl::{HTMLTag, Parser, VDom};
+
+// A course with fewer grades on the 7-point scale than this is pass/fail,
+// some pass/fail courses list the 7-point grades with a count of zero or a single graded student
+const MIN_GRADED: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Grade {
+    #[serde(rename = "12")]
+    Twelve,
+    #[serde(rename = "10")]
+    Ten,
+    #[serde(rename = "7")]
+    Seven,
+    #[serde(rename = "4")]
+    Four,
+    #[serde(rename = "02")]
+    Two,
+    #[serde(rename = "00")]
+    Zero,
+    #[serde(rename = "-3")]
+    MinusThree,
+    Passed,
+    Failed,
+    Absent,
+}
+
+impl Grade {
+    fn from_str(grade: &str) -> Result<Self> {
+        Ok(match grade.trim().to_lowercase().as_str() {
+            "12" => Grade::Twelve,
+            "10" => Grade::Ten,
+            "7" => Grade::Seven,
+            "4" => Grade::Four,
+            "02" => Grade::Two,
+            "00" => Grade::Zero,
+            "-3" => Grade::MinusThree,
+            "bestået" | "passed" => Grade::Passed,
+            "ikke bestået" | "failed" => Grade::Failed,
+            "ej mødt" | "absent" => Grade::Absent,
+            _ => bail!("Unknown grade: {grade}"),
+        })
+    }
+
+    /// The value of a grade on the 7-point scale
+    pub fn points(&self) -> Option<i32> {
+        match self {
+            Grade::Twelve => Some(12),
+            Grade::Ten => Some(10),
+            Grade::Seven => Some(7),
+            Grade::Four => Some(4),
+            Grade::Two => Some(2),
+            Grade::Zero => Some(0),
+            Grade::MinusThree => Some(-3),
+            Grade::Passed | Grade::Failed | Grade::Absent => None,
+        }
+    }
+
+    pub fn is_pass(&self) -> bool {
+        matches!(self, Grade::Passed) || self.points().is_some_and(|points| points >= 2)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GradeCount {
+    pub grade: Grade,
+    pub count: u32,
+}
+
+/// The grades of an exam and the statistics of them, the 7-point statistics are only there for graded exams
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExamStatistics {
+    pub grades: Vec<GradeCount>,
+    pub graded: bool,
+    pub total: u32,
+    pub pass: u32,
+    /// The failed students, including the absent ones
+    pub fail: u32,
+    pub absent: u32,
+    pub pass_rate: Option<f64>,
+    pub pass_rate_with_absent: Option<f64>,
+    pub mean: Option<f64>,
+    pub median: Option<f64>,
+    pub variance: Option<f64>,
+}
+
+/// The ordinary exam and the re-exam of a histogram page, either is missing if nobody took it (yet)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    pub exam: Option<ExamStatistics>,
+    pub re_exam: Option<ExamStatistics>,
+}
+
+impl ExamStatistics {
+    pub fn new(grades: Vec<GradeCount>) -> Self {
+        let count = |grade: Grade| {
+            grades
+                .iter()
+                .filter(|g| g.grade == grade)
+                .map(|g| g.count)
+                .sum()
+        };
+        let total = grades.iter().map(|g| g.count).sum();
+        let pass = grades
+            .iter()
+            .filter(|g| g.grade.is_pass())
+            .map(|g| g.count)
+            .sum();
+        let absent = count(Grade::Absent);
+        let fail = total - pass;
+        let rate = |pass: u32, total: u32| (total > 0).then(|| pass as f64 / total as f64);
+
+        // every graded student, lowest grade first
+        let mut points: Vec<f64> = vec![];
+        for grade in &grades {
+            if let Some(value) = grade.grade.points() {
+                points.extend(std::iter::repeat_n(value as f64, grade.count as usize));
+            }
+        }
+        points.sort_by(f64::total_cmp);
+        let graded = points.len() >= MIN_GRADED as usize;
+        let n = points.len() as f64;
+        let mean = graded.then(|| points.iter().sum::<f64>() / n);
+        let median = graded.then(|| {
+            let middle = points.len() / 2;
+            if points.len() % 2 == 1 {
+                points[middle]
+            } else {
+                (points[middle - 1] + points[middle]) / 2.0
+            }
+        });
+        let variance =
+            mean.map(|mean| points.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0));
+
+        Self {
+            graded,
+            total,
+            pass,
+            fail,
+            absent,
+            pass_rate: rate(pass, total - absent),
+            pass_rate_with_absent: rate(pass, total),
+            mean,
+            median,
+            variance,
+            grades,
+        }
+    }
+}
+
+pub fn parse_histogram(html: &str) -> Result<Histogram> {
+    let dom = tl::parse(html, tl::ParserOptions::default())?;
+    let parser = dom.parser();
+    // the exam and the re-exam each have a cell spanning the whole statistics table
+    let mut tables = exam_cells(&dom)
+        .into_iter()
+        .map(|cell| parse_grades(cell, parser));
+    let exam = tables.next().context("No exam table found")??;
+    let re_exam = tables.next().transpose()?.flatten();
+    Ok(Histogram {
+        exam: exam.map(ExamStatistics::new),
+        re_exam: re_exam.map(ExamStatistics::new),
+    })
+}
+
+fn exam_cells<'a>(dom: &'a VDom<'a>) -> Vec<&'a HTMLTag<'a>> {
+    dom.nodes()
+        .iter()
+        .filter_map(|node| node.as_tag())
+        .filter(|tag| tag.name() == "td")
+        .filter(|tag| {
+            tag.attributes()
+                .get("colspan")
+                .flatten()
+                .is_some_and(|colspan| colspan.as_utf8_str() == "2")
+        })
+        .collect()
+}
+
+// The rows of grade, count and percentage in the cell, None if the exam has no grades
+fn parse_grades(cell: &HTMLTag, parser: &Parser) -> Result<Option<Vec<GradeCount>>> {
+    let mut grades = vec![];
+    for row in cell.query_selector(parser, "tr").into_iter().flatten() {
+        let Some(row) = row.get(parser).and_then(|node| node.as_tag()) else {
+            continue;
+        };
+        let columns: Vec<String> = row
+            .children()
+            .top()
+            .iter()
+            .filter_map(|child| child.get(parser)?.as_tag())
+            .filter(|tag| tag.name() == "td")
+            .map(|td| td.inner_text(parser).trim().to_string())
+            .collect();
+        // the header row has th's instead
+        let [grade, count, _percentage] = columns.as_slice() else {
+            continue;
+        };
+        grades.push(GradeCount {
+            grade: Grade::from_str(grade)?,
+            count: count
+                .parse()
+                .with_context(|| format!("Invalid count of grade {grade}: {count}"))?,
+        });
+    }
+    Ok((!grades.is_empty()).then_some(grades))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> Histogram {
+        let html = std::fs::read_to_string(format!("./test_data/statistics/{name}.html")).unwrap();
+        parse_histogram(&html).unwrap()
+    }
+
+    fn close(a: Option<f64>, b: f64) -> bool {
+        a.is_some_and(|a| (a - b).abs() < 1e-9)
+    }
+
+    #[test]
+    fn test_graded_exam() {
+        let histogram = fixture("NDAA09023E-Winter-2022");
+        let exam = histogram.exam.unwrap();
+        assert_eq!(
+            exam.grades[0],
+            GradeCount {
+                grade: Grade::Twelve,
+                count: 5
+            }
+        );
+        assert!(exam.graded);
+        assert_eq!(
+            (exam.total, exam.pass, exam.fail, exam.absent),
+            (33, 29, 4, 3)
+        );
+        assert!(close(exam.pass_rate, 29.0 / 30.0));
+        assert!(close(exam.pass_rate_with_absent, 29.0 / 33.0));
+        assert!(close(exam.mean, 230.0 / 30.0));
+        assert!(close(exam.median, 7.0));
+        assert!(close(exam.variance, 10.98850574712644));
+
+        // too few graded students to treat it as a graded exam
+        let re_exam = histogram.re_exam.unwrap();
+        assert!(!re_exam.graded);
+        assert_eq!(re_exam.mean, None);
+        assert!(close(re_exam.pass_rate, 0.75));
+    }
+
+    #[test]
+    fn test_pass_fail_exam() {
+        let histogram = fixture("NDAB15009E-Summer-2023");
+        let exam = histogram.exam.unwrap();
+        assert!(!exam.graded);
+        assert_eq!(
+            (exam.total, exam.pass, exam.fail, exam.absent),
+            (50, 40, 10, 4)
+        );
+        assert!(close(exam.pass_rate, 40.0 / 46.0));
+        assert_eq!(exam.median, None);
+        // the re-exam has not been held yet
+        assert_eq!(histogram.re_exam, None);
+    }
+
+    #[test]
+    fn test_median_of_odd_count() {
+        let grades = [(Grade::Twelve, 2), (Grade::Four, 2), (Grade::Two, 1)]
+            .map(|(grade, count)| GradeCount { grade, count })
+            .to_vec();
+        assert!(close(ExamStatistics::new(grades).median, 4.0));
+    }
+
+    #[test]
+    fn test_not_a_histogram() {
+        assert!(parse_histogram("<html><body>Server error</body></html>").is_err());
+        let unknown = "<table><tr><td colspan=\"2\"><table><tr><td>13</td><td>1</td><td>100 %</td></tr></table></td></tr></table>";
+        assert!(parse_histogram(unknown).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+// Checks that a parsed course makes sense as a whole, every field can parse fine on its own
+// and still contradict the others, e.g. a course over two blocks that is only given one block
+use crate::parser::{Block, Course, Duration, Exam, Schedule};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+// The hours of work an ECTS point stands for, 7.5 ECTS is 206 hours
+const HOURS_PER_ECTS: f32 = 27.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Unusual, but there are courses like this
+    Info,
+    /// Probably wrong on the page or in the parser
+    Warning,
+    /// Can not be right
+    Error,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "info" => Ok(Severity::Info),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(format!(
+                "unknown severity {s}, expected info, warning or error"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Something questionable about a course, `rule` names the check that found it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub rule: String,
+    pub field: String,
+    pub message: String,
+}
+
+impl Finding {
+    fn new(severity: Severity, rule: &str, field: &str, message: String) -> Self {
+        Self {
+            severity,
+            rule: rule.to_string(),
+            field: field.to_string(),
+            message,
+        }
+    }
+}
+
+/// Every finding of the checks on the course, the most severe first
+pub fn validate(course: &Course) -> Vec<Finding> {
+    let mut findings = vec![];
+    check_duration(course, &mut findings);
+    check_workload(course, &mut findings);
+    check_summer_schedule(course, &mut findings);
+    check_coordinators(course, &mut findings);
+    check_exams(course, &mut findings);
+    findings.sort_by_key(|finding| std::cmp::Reverse(finding.severity));
+    findings
+}
+
+fn check_duration(course: &Course, findings: &mut Vec<Finding>) {
+    let blocks = course
+        .info
+        .block
+        .iter()
+        .filter(|block| !matches!(block, Block::Other(_)))
+        .count();
+    let expected = match course.info.duration {
+        Duration::One => 1,
+        Duration::Two => 2,
+        Duration::DependsOnEcts | Duration::Custom(_) => return,
+    };
+    if blocks != expected {
+        findings.push(Finding::new(
+            Severity::Warning,
+            "duration_blocks",
+            "info.block",
+            format!(
+                "the duration is {:?} blocks but the course is in {blocks} blocks",
+                course.info.duration
+            ),
+        ));
+    }
+}
+
+fn check_workload(course: &Course, findings: &mut Vec<Finding>) {
+    let ects = course.info.ects;
+    if ects <= 0.0 {
+        findings.push(Finding::new(
+            Severity::Error,
+            "ects",
+            "info.ects",
+            format!("the course is {ects} ECTS"),
+        ));
+        return;
+    }
+    if course.workloads.is_empty() {
+        return;
+    }
+    let hours: f32 = course.workloads.iter().map(|w| w.hours).sum();
+    let expected = ects * HOURS_PER_ECTS;
+    let deviation = (hours - expected).abs() / expected;
+    let severity = if deviation > 0.5 {
+        Severity::Error
+    } else if deviation > 0.2 {
+        Severity::Warning
+    } else {
+        return;
+    };
+    findings.push(Finding::new(
+        severity,
+        "workload_hours",
+        "workloads",
+        format!("{hours} hours of work for {ects} ECTS, expected about {expected}"),
+    ));
+}
+
+// The summer courses are taught outside of the schedule groups of the blocks
+fn check_summer_schedule(course: &Course, findings: &mut Vec<Finding>) {
+    if !course.info.block.contains(&Block::Summer) {
+        return;
+    }
+    let groups = course
+        .info
+        .schedule
+        .iter()
+        .filter(|schedule| {
+            matches!(
+                schedule,
+                Schedule::A | Schedule::B | Schedule::C | Schedule::D
+            )
+        })
+        .count();
+    if groups > 0 {
+        findings.push(Finding::new(
+            Severity::Warning,
+            "summer_schedule",
+            "info.schedule",
+            format!("a summer course in {groups} schedule groups"),
+        ));
+    }
+}
+
+fn check_coordinators(course: &Course, findings: &mut Vec<Finding>) {
+    if course.logistics.coordinators.is_empty() {
+        findings.push(Finding::new(
+            Severity::Warning,
+            "no_coordinators",
+            "logistics.coordinators",
+            "the course has no coordinators".to_string(),
+        ));
+    }
+}
+
+fn check_exams(course: &Course, findings: &mut Vec<Finding>) {
+    const DAY: u32 = 24 * 60;
+    for (i, exam) in course.exams.iter().enumerate() {
+        let field = format!("exams[{i}]");
+        let finding = match exam {
+            Exam::Oral(Some(minutes)) if *minutes > DAY => Some((Severity::Error, minutes)),
+            Exam::Oral(Some(minutes)) if *minutes > 3 * 60 => Some((Severity::Warning, minutes)),
+            // take-home exams can take a week, but not an exam at a desk
+            Exam::Itx(Some(minutes)) if *minutes > DAY => Some((Severity::Error, minutes)),
+            Exam::Written(Some(minutes)) if *minutes > DAY => Some((Severity::Info, minutes)),
+            _ => None,
+        };
+        if let Some((severity, minutes)) = finding {
+            findings.push(Finding::new(
+                severity,
+                "exam_duration",
+                &field,
+                format!(
+                    "the exam is {minutes} minutes, {:.1} hours",
+                    *minutes as f32 / 60.0
+                ),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{test_course, Workload, WorkloadType};
+
+    fn rules(course: &Course) -> Vec<(Severity, String)> {
+        validate(course)
+            .into_iter()
+            .map(|finding| (finding.severity, finding.rule))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_courses() {
+        for page in [
+            "LSLS10061U",
+            "LSLS10156U",
+            "NDAB15009U",
+            "NDAB22007U",
+            "NFYK13004U",
+            "NMAA04032U",
+        ] {
+            assert_eq!(rules(&test_course(page)), [], "{page}");
+        }
+    }
+
+    #[test]
+    fn test_findings() {
+        let mut course = test_course("LSLS10061U");
+        course.info.duration = Duration::Two;
+        course.info.block = vec![Block::Summer];
+        course.info.schedule = vec![Schedule::B];
+        course.logistics.coordinators.clear();
+        course.workloads = vec![Workload::new(WorkloadType::Lectures, 60.0)];
+        course.exams = vec![Exam::Oral(Some(3 * 24 * 60)), Exam::Written(Some(4 * 60))];
+
+        let findings = validate(&course);
+        assert_eq!(findings[1].field, "exams[0]");
+        assert_eq!(
+            rules(&course),
+            [
+                (Severity::Error, "workload_hours".to_string()),
+                (Severity::Error, "exam_duration".to_string()),
+                (Severity::Warning, "duration_blocks".to_string()),
+                (Severity::Warning, "summer_schedule".to_string()),
+                (Severity::Warning, "no_coordinators".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_severity() {
+        assert!(Severity::Error > Severity::Warning);
+        assert_eq!("warning".parse(), Ok(Severity::Warning));
+        assert!("fatal".parse::<Severity>().is_err());
+        assert_eq!(serde_json::to_string(&Severity::Info).unwrap(), r#""info""#);
+    }
+}
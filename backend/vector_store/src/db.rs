@@ -98,7 +98,7 @@ impl PostgresDB {
         // if it is, do nothing
         let result = query!(
             "SELECT title, content FROM course WHERE id = $1",
-            document.info.id
+            document.info.id.as_str()
         )
         .fetch_optional(&self.pool)
         .await?;
@@ -114,7 +114,7 @@ impl PostgresDB {
         query!(
             "INSERT INTO course (id, title, content) VALUES ($1, $2, $3)
              ON CONFLICT(id) DO UPDATE SET title = $2, content = $3, last_modified = CURRENT_TIMESTAMP",
-            document.info.id,
+            document.info.id.as_str(),
             document.title,
             document.description.content
         ).execute(&mut *tx).await?;
@@ -122,7 +122,7 @@ impl PostgresDB {
         // A coordinator may have been removed, so we need to delete all coordinators for this course
         query!(
             "DELETE FROM course_coordinator WHERE course_id = $1",
-            document.info.id
+            document.info.id.as_str()
         )
         .execute(&mut *tx)
         .await?;
@@ -140,7 +140,7 @@ impl PostgresDB {
 
             query!(
                 "INSERT INTO course_coordinator (course_id, email) VALUES ($1, $2)",
-                document.info.id,
+                document.info.id.as_str(),
                 coordinator.email
             )
             .execute(&mut *tx)
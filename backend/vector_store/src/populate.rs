@@ -1,33 +1,12 @@
-use super::{Coordinator, PostgresDB};
+use super::PostgresDB;
 use anyhow::Result;
 use nanohtml2text::html2text;
-use serde::Deserialize;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
 
-#[derive(Deserialize, Clone)]
-pub struct Document {
-    pub title: String,
-    pub info: Info,
-    pub description: Description,
-    pub logistics: Logistics,
-}
-
-#[derive(Deserialize, Clone)]
-pub struct Logistics {
-    pub coordinators: Vec<Coordinator>,
-}
-
-#[derive(Deserialize, Clone)]
-pub struct Description {
-    pub content: String,
-}
-
-#[derive(Deserialize, Clone)]
-pub struct Info {
-    pub id: String,
-}
+/// The courses are read with the model of the parser that wrote them
+pub type Document = rust_parser::parser::Course;
 
 /// Upserts all the documents in the directory into the database
 /// This function is used to populate the database
@@ -56,6 +35,7 @@ fn read_json(path: &Path) -> Result<Document> {
 
 /// Reads all the jsons in the directory and returns a Vec<Document>
 /// This function also converts the html content to plain text and removes newlines
+/// Files that can not be read as a course are skipped, so one bad file does not stop the population
 /// This function is used to populate the database
 /// TODO: Remove population functionality from this service
 fn read_jsons(path: &Path) -> Result<Vec<Document>> {
@@ -65,8 +45,15 @@ fn read_jsons(path: &Path) -> Result<Vec<Document>> {
     for file_name in file_names {
         let file_name = file_name?;
         let path = file_name.path();
-        let document = read_json(&path)?;
-        documents.push(document);
+        // the parser keeps its manifest as a hidden file next to the courses
+        let is_hidden = file_name.file_name().to_string_lossy().starts_with('.');
+        if is_hidden || path.extension().map_or(true, |ext| ext != "json") {
+            continue;
+        }
+        match read_json(&path) {
+            Ok(document) => documents.push(document),
+            Err(e) => println!("skipping {}: {e:#}", path.display()),
+        }
     }
     Ok(documents)
 }